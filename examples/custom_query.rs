@@ -22,5 +22,5 @@ async fn main() {
 
     let client = NyaaClient::new();
     let res = client.get(&query).await.unwrap();
-    println!("{:#?}", res.get(0).unwrap());
+    println!("{:#?}", res.first().unwrap());
 }
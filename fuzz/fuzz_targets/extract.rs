@@ -0,0 +1,7 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|html: &str| {
+    let _ = nyaa_si::extractor::extract(html, "https://nyaa.si");
+});
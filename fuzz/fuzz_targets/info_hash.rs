@@ -0,0 +1,11 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use nyaa_si::lookup::InfoHash;
+
+fuzz_target!(|s: &str| {
+    let _ = InfoHash::from_magnet(s);
+    if let Ok(hash) = s.parse::<InfoHash>() {
+        assert_eq!(hash.to_string().parse::<InfoHash>().unwrap(), hash);
+    }
+});
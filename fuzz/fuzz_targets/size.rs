@@ -0,0 +1,11 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use nyaa_si::model::Size;
+
+fuzz_target!(|s: &str| {
+    if let Ok(size) = s.parse::<Size>() {
+        let _ = size.to_string().parse::<Size>();
+        let _ = size.partial_cmp(&Size::MB(1.0));
+    }
+});
@@ -0,0 +1,8 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use nyaa_si::lookup::InfoHash;
+
+fuzz_target!(|data: &[u8]| {
+    let _ = InfoHash::from_torrent_file(data);
+});
@@ -0,0 +1,9 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use nyaa_si::model::TorrentId;
+
+fuzz_target!(|s: &str| {
+    let _ = TorrentId::from_url(s);
+    let _ = s.parse::<TorrentId>();
+});
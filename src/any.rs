@@ -0,0 +1,375 @@
+//! Runtime dispatch across sites, for searches whose site comes from
+//! configuration.
+//!
+//! ```no_run
+//! use nyaa_si::{any::{AnyCategory, AnySite}, QueryBuilder};
+//!
+//! # async fn run() -> nyaa_si::error::Result<()> {
+//! let site = AnySite::from_site("sukebei");
+//! let category: AnyCategory = "sukebei:Art - Manga".parse()?;
+//! let query = QueryBuilder::new().category(category).build();
+//! let torrents = site.get(&query).await?;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::fmt::Display;
+use std::str::FromStr;
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::{
+    category::Category,
+    client::Client,
+    dynamic::DynamicCategory,
+    error::{Error, Result},
+    model::Torrent,
+    nyaa::{NyaaCategory, NyaaClient},
+    query::Query,
+    session::{CookieJar, Session},
+    sukebei::{SukebeiCategory, SukebeiClient},
+    transport::{ReqwestTransport, Transport},
+};
+
+/// A category of any site
+///
+/// Parsing and serde use `site:category`, with the site being `nyaa`,
+/// `sukebei` or `custom` and defaulting to `nyaa`, e.g. `sukebei:1_4` or
+/// `Anime - Raw`. Custom categories only parse by code, as their names
+/// depend on the instance. `Display` gives the bare code, as used in urls.
+#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq)]
+pub enum AnyCategory {
+    Nyaa(NyaaCategory),
+    Sukebei(SukebeiCategory),
+    /// a category of a custom instance
+    Custom(DynamicCategory),
+}
+
+impl AnyCategory {
+    /// `nyaa`, `sukebei` or `custom`
+    pub fn site(&self) -> &'static str {
+        match self {
+            AnyCategory::Nyaa(_) => "nyaa",
+            AnyCategory::Sukebei(_) => "sukebei",
+            AnyCategory::Custom(_) => "custom",
+        }
+    }
+
+    fn is_all(&self) -> bool {
+        self.code() == "0_0"
+    }
+
+    fn mismatch(&self, site: &str) -> Error {
+        Error::CategoryError(format!(
+            "{} has no category {}:{}",
+            site,
+            self.site(),
+            self
+        ))
+    }
+}
+
+impl Default for AnyCategory {
+    fn default() -> Self {
+        AnyCategory::Nyaa(NyaaCategory::default())
+    }
+}
+
+impl From<NyaaCategory> for AnyCategory {
+    fn from(category: NyaaCategory) -> Self {
+        AnyCategory::Nyaa(category)
+    }
+}
+
+impl From<SukebeiCategory> for AnyCategory {
+    fn from(category: SukebeiCategory) -> Self {
+        AnyCategory::Sukebei(category)
+    }
+}
+
+impl From<DynamicCategory> for AnyCategory {
+    fn from(category: DynamicCategory) -> Self {
+        AnyCategory::Custom(category)
+    }
+}
+
+/// `All` of any site converts to `All`, other categories only to their own
+/// site's.
+impl TryFrom<AnyCategory> for NyaaCategory {
+    type Error = Error;
+
+    fn try_from(category: AnyCategory) -> Result<Self> {
+        match category {
+            AnyCategory::Nyaa(category) => Ok(category),
+            _ if category.is_all() => Ok(NyaaCategory::All),
+            _ => Err(category.mismatch("nyaa")),
+        }
+    }
+}
+
+impl TryFrom<AnyCategory> for SukebeiCategory {
+    type Error = Error;
+
+    fn try_from(category: AnyCategory) -> Result<Self> {
+        match category {
+            AnyCategory::Sukebei(category) => Ok(category),
+            _ if category.is_all() => Ok(SukebeiCategory::All),
+            _ => Err(category.mismatch("sukebei")),
+        }
+    }
+}
+
+/// Custom instances accept any category by its code
+impl From<AnyCategory> for DynamicCategory {
+    fn from(category: AnyCategory) -> Self {
+        match category {
+            AnyCategory::Custom(category) => category,
+            category => category.code().parse().unwrap_or_default(),
+        }
+    }
+}
+
+impl Display for AnyCategory {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.code())
+    }
+}
+
+impl FromStr for AnyCategory {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let (site, category) = match s.split_once(':') {
+            Some((site, category)) => (site.trim(), category),
+            None => ("nyaa", s),
+        };
+        match site {
+            "nyaa" => category.parse().map(AnyCategory::Nyaa),
+            "sukebei" => category.parse().map(AnyCategory::Sukebei),
+            "custom" => category.parse().map(AnyCategory::Custom),
+            _ => Err(Error::CategoryError(format!("Unknown site {}", site))),
+        }
+    }
+}
+
+impl Serialize for AnyCategory {
+    fn serialize<S: Serializer>(
+        &self,
+        serializer: S,
+    ) -> std::result::Result<S::Ok, S::Error> {
+        serializer.serialize_str(&format!("{}:{}", self.site(), self.code()))
+    }
+}
+
+impl<'de> Deserialize<'de> for AnyCategory {
+    fn deserialize<D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> std::result::Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        s.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+impl Category for AnyCategory {
+    fn all() -> Vec<Self> {
+        let nyaa = NyaaCategory::all().into_iter().map(AnyCategory::Nyaa);
+        let sukebei =
+            SukebeiCategory::all().into_iter().map(AnyCategory::Sukebei);
+        let custom =
+            DynamicCategory::all().into_iter().map(AnyCategory::Custom);
+        nyaa.chain(sukebei).chain(custom).collect()
+    }
+
+    fn code(&self) -> &'static str {
+        match self {
+            AnyCategory::Nyaa(category) => category.code(),
+            AnyCategory::Sukebei(category) => category.code(),
+            AnyCategory::Custom(category) => category.code(),
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            AnyCategory::Nyaa(category) => category.name(),
+            AnyCategory::Sukebei(category) => category.name(),
+            AnyCategory::Custom(category) => category.name(),
+        }
+    }
+
+    fn description(&self) -> &'static str {
+        match self {
+            AnyCategory::Nyaa(category) => category.description(),
+            AnyCategory::Sukebei(category) => category.description(),
+            AnyCategory::Custom(category) => category.description(),
+        }
+    }
+
+    fn parent(&self) -> Option<Self> {
+        match self {
+            AnyCategory::Nyaa(category) => {
+                category.parent().map(AnyCategory::Nyaa)
+            }
+            AnyCategory::Sukebei(category) => {
+                category.parent().map(AnyCategory::Sukebei)
+            }
+            AnyCategory::Custom(category) => {
+                category.parent().map(AnyCategory::Custom)
+            }
+        }
+    }
+}
+
+/// A client of nyaa, sukebei or a custom NyaaV2 instance
+#[derive(Debug)]
+pub enum AnySite<T = ReqwestTransport> {
+    Nyaa(NyaaClient<T>),
+    Sukebei(SukebeiClient<T>),
+    /// an anonymous session on a custom instance
+    Custom(Session<DynamicCategory, T>),
+}
+
+impl AnySite {
+    /// `nyaa`, `sukebei`, their domains, or the url of a custom instance
+    pub fn from_site(site: &str) -> Self {
+        Self::with_transport(site, ReqwestTransport::new())
+    }
+}
+
+impl<T: Transport> AnySite<T> {
+    pub fn with_transport(site: &str, transport: T) -> Self {
+        let site = site.trim().trim_end_matches('/');
+        let host = site
+            .strip_prefix("https://")
+            .or_else(|| site.strip_prefix("http://"))
+            .unwrap_or(site);
+        match host {
+            "nyaa" | "nyaa.si" => {
+                AnySite::Nyaa(NyaaClient::with_transport(transport))
+            }
+            "sukebei" | "sukebei.nyaa.si" => {
+                AnySite::Sukebei(SukebeiClient::with_transport(transport))
+            }
+            _ => {
+                let base_url = if host == site {
+                    format!("https://{}", site)
+                } else {
+                    site.to_string()
+                };
+                AnySite::Custom(Session::with_transport(
+                    transport,
+                    base_url,
+                    CookieJar::new(),
+                ))
+            }
+        }
+    }
+
+    pub fn base_url(&self) -> &str {
+        match self {
+            AnySite::Nyaa(_) => <NyaaClient<T>>::BASE_URL,
+            AnySite::Sukebei(_) => <SukebeiClient<T>>::BASE_URL,
+            AnySite::Custom(session) => session.base_url(),
+        }
+    }
+
+    /// Send a query to the site, failing if its category belongs to another
+    /// site
+    #[tracing::instrument(skip(self, query))]
+    pub async fn get(
+        &self,
+        query: &Query<AnyCategory>,
+    ) -> Result<Vec<Torrent>> {
+        match self {
+            AnySite::Nyaa(client) => {
+                let query = query.try_map_category(|category| {
+                    NyaaCategory::try_from(*category)
+                })?;
+                client.get(&query).await
+            }
+            AnySite::Sukebei(client) => {
+                let query = query.try_map_category(|category| {
+                    SukebeiCategory::try_from(*category)
+                })?;
+                client.get(&query).await
+            }
+            AnySite::Custom(session) => {
+                let query = query.try_map_category(|category| {
+                    Ok::<_, Error>(DynamicCategory::from(*category))
+                })?;
+                session.get(&query).await
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::torrent;
+    use crate::testing::{FakeNyaa, FakeTorrent};
+    use crate::QueryBuilder;
+
+    #[test]
+    fn parse_any_category() {
+        let category: AnyCategory = "Anime - Raw".parse().unwrap();
+        assert_eq!(category, AnyCategory::Nyaa(NyaaCategory::AnimeRaw));
+        let category: AnyCategory = "sukebei:1_4".parse().unwrap();
+        assert_eq!(category, AnyCategory::Sukebei(SukebeiCategory::ArtManga));
+        assert_eq!(category.to_string(), "1_4");
+        assert!("tokyotosho:1_0".parse::<AnyCategory>().is_err());
+
+        let json = serde_json::to_string(&category).unwrap();
+        assert_eq!(json, "\"sukebei:1_4\"");
+        assert_eq!(
+            serde_json::from_str::<AnyCategory>(&json).unwrap(),
+            category
+        );
+
+        assert!(AnyCategory::Nyaa(NyaaCategory::Anime)
+            .contains(&NyaaCategory::AnimeRaw.into()));
+        assert!(!AnyCategory::Nyaa(NyaaCategory::All)
+            .contains(&SukebeiCategory::ArtManga.into()));
+    }
+
+    #[tokio::test]
+    async fn dispatch_by_site() {
+        let torrent = torrent(1).build();
+        let server = FakeNyaa::new()
+            .torrent(FakeTorrent::new(torrent).category(NyaaCategory::AnimeRaw))
+            .start()
+            .unwrap();
+        let query = |category: AnyCategory| {
+            QueryBuilder::new()
+                .search("show")
+                .category(category)
+                .build()
+        };
+
+        let nyaa =
+            AnySite::with_transport("https://nyaa.si/", server.transport());
+        assert_eq!(nyaa.base_url(), "https://nyaa.si");
+        let res = nyaa.get(&query(NyaaCategory::Anime.into())).await.unwrap();
+        assert_eq!(res[0].link, "https://nyaa.si/download/1.torrent");
+        let err = nyaa
+            .get(&query(SukebeiCategory::ArtAnime.into()))
+            .await
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Category error: nyaa has no category sukebei:1_1"
+        );
+
+        let sukebei = AnySite::with_transport("sukebei", server.transport());
+        let res = sukebei.get(&query(AnyCategory::default())).await.unwrap();
+        assert_eq!(res[0].link, "https://sukebei.nyaa.si/download/1.torrent");
+
+        let custom =
+            AnySite::with_transport("nyaa.example.org", server.transport());
+        assert_eq!(custom.base_url(), "https://nyaa.example.org");
+        let res = custom
+            .get(&query(NyaaCategory::AnimeRaw.into()))
+            .await
+            .unwrap();
+        assert_eq!(res.len(), 1);
+    }
+}
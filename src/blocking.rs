@@ -0,0 +1,209 @@
+//! Synchronous clients that do not need an async runtime.
+//!
+//! These must not be called from within an async context, use
+//! [`crate::NyaaClient`] and [`crate::SukebeiClient`] there instead.
+//!
+//! Only searching and layout checks are available: sessions (`login` and
+//! `resume`) and custom transports are async only.
+
+use std::sync::Arc;
+
+use tracing::{event, Level};
+
+use crate::{
+    canary::{self, HealthReport},
+    category::Category,
+    client::Client,
+    error::{Error, Result},
+    extractor::Extractor,
+    model::Torrent,
+    nyaa::{self, NyaaCategory},
+    query::Query,
+    sukebei::{self, SukebeiCategory},
+};
+
+#[derive(Debug)]
+pub struct NyaaClient {
+    inner: Inner,
+}
+
+impl Default for NyaaClient {
+    fn default() -> Self {
+        Self {
+            inner: Inner::new(<nyaa::NyaaClient>::BASE_URL),
+        }
+    }
+}
+
+impl NyaaClient {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Parse results with a custom extractor, e.g. to follow markup changes
+    pub fn with_extractor<E: Into<Arc<Extractor>>>(
+        mut self,
+        extractor: E,
+    ) -> Self {
+        self.inner.extractor = extractor.into();
+        self
+    }
+
+    /// Send a query to the api
+    #[tracing::instrument(skip(self))]
+    pub fn get(&self, query: &Query<NyaaCategory>) -> Result<Vec<Torrent>> {
+        self.inner.get(query)
+    }
+
+    /// Fetch a search that should have results and check its page still has
+    /// the layout the extractor expects
+    pub fn check_layout(
+        &self,
+        query: &Query<NyaaCategory>,
+    ) -> Result<HealthReport> {
+        self.inner.check_layout(query)
+    }
+}
+
+#[derive(Debug)]
+pub struct SukebeiClient {
+    inner: Inner,
+}
+
+impl Default for SukebeiClient {
+    fn default() -> Self {
+        Self {
+            inner: Inner::new(<sukebei::SukebeiClient>::BASE_URL),
+        }
+    }
+}
+
+impl SukebeiClient {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Parse results with a custom extractor, e.g. to follow markup changes
+    pub fn with_extractor<E: Into<Arc<Extractor>>>(
+        mut self,
+        extractor: E,
+    ) -> Self {
+        self.inner.extractor = extractor.into();
+        self
+    }
+
+    /// Send a query to the api
+    #[tracing::instrument(skip(self))]
+    pub fn get(&self, query: &Query<SukebeiCategory>) -> Result<Vec<Torrent>> {
+        self.inner.get(query)
+    }
+
+    /// Fetch a search that should have results and check its page still has
+    /// the layout the extractor expects
+    pub fn check_layout(
+        &self,
+        query: &Query<SukebeiCategory>,
+    ) -> Result<HealthReport> {
+        self.inner.check_layout(query)
+    }
+}
+
+/// What both blocking clients share
+#[derive(Debug)]
+struct Inner {
+    http: reqwest::blocking::Client,
+    extractor: Arc<Extractor>,
+    base_url: String,
+}
+
+impl Inner {
+    fn new<S: Into<String>>(base_url: S) -> Self {
+        Self {
+            http: reqwest::blocking::Client::new(),
+            extractor: Arc::default(),
+            base_url: base_url.into(),
+        }
+    }
+
+    fn fetch<C: Category>(
+        &self,
+        query: &Query<C>,
+    ) -> Result<(String, reqwest::StatusCode, String)> {
+        let url = format!("{}/?{}", self.base_url, query);
+
+        event!(Level::DEBUG, "url = {}", url);
+
+        let res = self.http.get(&url).send()?;
+        let status = res.status();
+        Ok((url, status, res.text()?))
+    }
+
+    fn get<C: Category>(&self, query: &Query<C>) -> Result<Vec<Torrent>> {
+        let (_, status, page) = self.fetch(query)?;
+        if !status.is_success() {
+            return Err(Error::StatusError(status.as_u16()));
+        }
+
+        self.extractor.extract(&page, &self.base_url)
+    }
+
+    fn check_layout<C: Category>(
+        &self,
+        query: &Query<C>,
+    ) -> Result<HealthReport> {
+        let (url, status, page) = self.fetch(query)?;
+        Ok(canary::report(
+            url,
+            status,
+            &page,
+            &self.extractor,
+            &self.base_url,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::canary::Problem;
+    use crate::mock::{MockResponse, MockServer, LISTING};
+    use crate::query::QueryBuilder;
+
+    fn client(server: &MockServer) -> NyaaClient {
+        NyaaClient {
+            inner: Inner::new(server.url()),
+        }
+    }
+
+    #[test]
+    fn get_without_runtime() {
+        let server = MockServer::start(|req| {
+            assert_eq!(req.path, "/?q=show&p=1&s=seeders&o=desc&f=0&c=1_0");
+            MockResponse::html(LISTING)
+        })
+        .unwrap();
+        let query = QueryBuilder::new()
+            .search("show")
+            .category(NyaaCategory::Anime)
+            .build();
+
+        let res = client(&server).get(&query).unwrap();
+        assert_eq!(res.len(), 1);
+        assert_eq!(res[0].title, "[Group] Show - 01");
+        assert_eq!(res[0].link, format!("{}/download/1.torrent", server.url()));
+        assert_eq!(res[0].seeders, 10);
+    }
+
+    #[test]
+    fn error_status() {
+        let server = MockServer::start(|_| MockResponse::status(503)).unwrap();
+        let query = QueryBuilder::new().build();
+        let client = client(&server);
+
+        let err = client.get(&query).unwrap_err();
+        assert!(matches!(err, Error::StatusError(503)));
+        let report = client.check_layout(&query).unwrap();
+        assert_eq!(report.status, 503);
+        assert_eq!(report.problems[0], Problem::Status(503));
+    }
+}
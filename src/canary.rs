@@ -0,0 +1,367 @@
+//! Layout drift detection.
+//!
+//! A search that should always have results is fetched and its page checked
+//! against what the extractor expects, so a markup change shows up as a
+//! failing [`HealthReport`] instead of pollers quietly getting no torrents.
+
+use std::fmt::Display;
+use std::sync::OnceLock;
+
+use http::{Method, StatusCode};
+use scraper::{ElementRef, Html, Selector};
+use serde::Serialize;
+use tracing::{event, Level};
+
+use crate::{
+    category::Category,
+    error::Result,
+    extractor::{Columns, Extractor},
+    model::Torrent,
+    query::Query,
+    transport::{self, RequestBuilderExt, ResponseExt, Transport},
+};
+
+static TABLE_SELECTOR: OnceLock<Selector> = OnceLock::new();
+static HEADER_SELECTOR: OnceLock<Selector> = OnceLock::new();
+
+/// Unix time of 2008-01-01, before the oldest torrent on the sites
+const OLDEST_TIMESTAMP: i64 = 1_199_145_600;
+
+/// Outcome of checking one search result page
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct HealthReport {
+    /// the fetched search, empty for [`check_page`]
+    pub url: String,
+    pub status: u16,
+    /// rows matched by the extractor's row selector
+    pub rows: usize,
+    /// rows that parsed into a torrent
+    pub parsed: usize,
+    pub problems: Vec<Problem>,
+}
+
+impl HealthReport {
+    pub fn is_healthy(&self) -> bool {
+        self.problems.is_empty()
+    }
+}
+
+/// Something on the page the extractor does not expect
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub enum Problem {
+    Status(u16),
+    MissingTable,
+    /// class of a missing or misplaced `<th>`
+    MissingHeader(String),
+    NoRows,
+    Row {
+        row: usize,
+        error: String,
+    },
+    Timestamp {
+        row: usize,
+        timestamp: i64,
+    },
+    Magnet {
+        row: usize,
+        magnet: String,
+    },
+}
+
+impl Display for Problem {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Problem::Status(status) => {
+                write!(f, "unexpected status {}", status)
+            }
+            Problem::MissingTable => write!(f, "no listing table"),
+            Problem::MissingHeader(class) => {
+                write!(f, "header {} missing or moved", class)
+            }
+            Problem::NoRows => write!(f, "no rows"),
+            Problem::Row { row, error } => write!(f, "row {}: {}", row, error),
+            Problem::Timestamp { row, timestamp } => {
+                write!(f, "row {}: implausible timestamp {}", row, timestamp)
+            }
+            Problem::Magnet { row, magnet } => {
+                write!(f, "row {}: malformed magnet {}", row, magnet)
+            }
+        }
+    }
+}
+
+/// Check a fetched search result page.
+///
+/// `base_url` is only used to build the links of the parsed torrents.
+pub fn check_page(
+    html: &str,
+    extractor: &Extractor,
+    base_url: &str,
+) -> HealthReport {
+    let document = Html::parse_document(html);
+    let mut problems = Vec::new();
+
+    // the table holding the rows, or any table on pages without rows
+    let first_row = document.select(extractor.row_selector()).next();
+    let table = first_row
+        .and_then(|row| {
+            row.ancestors()
+                .filter_map(ElementRef::wrap)
+                .find(|element| element.value().name() == "table")
+        })
+        .or_else(|| {
+            let selector = TABLE_SELECTOR
+                .get_or_init(|| Selector::parse("table").unwrap());
+            document.select(selector).next()
+        });
+    if table.is_none() {
+        problems.push(Problem::MissingTable);
+    }
+
+    // header classes per grid column, spanned cells repeated
+    let header =
+        HEADER_SELECTOR.get_or_init(|| Selector::parse("thead th").unwrap());
+    let headers: Vec<_> = table
+        .iter()
+        .flat_map(|table| table.select(header))
+        .flat_map(|th| {
+            let classes: Vec<_> = th.value().classes().collect();
+            std::iter::repeat(classes).take(colspan(th))
+        })
+        .collect();
+    // the grid column of each `<td>`, which the extractor's columns count
+    let cells: Vec<usize> = first_row
+        .iter()
+        .flat_map(|row| row.child_elements())
+        .filter(|cell| cell.value().name() == "td")
+        .scan(0, |grid, cell| {
+            let start = *grid;
+            *grid += colspan(cell);
+            Some(start)
+        })
+        .collect();
+    for (column, class) in expected_headers(&extractor.config().columns) {
+        let found = column
+            .checked_sub(1)
+            .map(|index| cells.get(index).copied().unwrap_or(index))
+            .and_then(|grid| headers.get(grid))
+            .is_some_and(|th| th.contains(&class));
+        if !found {
+            problems.push(Problem::MissingHeader(class.to_string()));
+        }
+    }
+
+    let now = chrono::Utc::now().timestamp();
+    let (mut rows, mut parsed) = (0, 0);
+    for (row, torrent) in
+        extractor.extract_rows(&document, base_url).enumerate()
+    {
+        rows += 1;
+        match torrent {
+            Ok(torrent) => {
+                parsed += 1;
+                problems.extend(check_torrent(row, &torrent, now));
+            }
+            Err(err) => problems.push(Problem::Row {
+                row,
+                error: err.to_string(),
+            }),
+        }
+    }
+    if rows == 0 {
+        problems.push(Problem::NoRows);
+    }
+
+    HealthReport {
+        url: String::new(),
+        status: 200,
+        rows,
+        parsed,
+        problems,
+    }
+}
+
+/// Clamped like browsers do
+fn colspan(cell: ElementRef<'_>) -> usize {
+    cell.value()
+        .attr("colspan")
+        .and_then(|span| span.parse().ok())
+        .unwrap_or(1)
+        .clamp(1, 1000)
+}
+
+/// The `<th>` class of each column the extractor reads, by position
+fn expected_headers(columns: &Columns) -> Vec<(usize, &'static str)> {
+    let mut headers = vec![
+        (columns.category, "hdr-category"),
+        (columns.name, "hdr-name"),
+        (columns.links, "hdr-link"),
+        (columns.size, "hdr-size"),
+        (columns.date, "hdr-date"),
+        (columns.seeders, "hdr-seeders"),
+        (columns.leechers, "hdr-leechers"),
+        (columns.downloads, "hdr-downloads"),
+    ];
+    headers.sort();
+    headers
+}
+
+fn check_torrent(row: usize, torrent: &Torrent, now: i64) -> Vec<Problem> {
+    let mut problems = Vec::new();
+    let timestamp = torrent.date.timestamp();
+    // a day of slack for clock skew
+    if !(OLDEST_TIMESTAMP..=now + 86_400).contains(&timestamp) {
+        problems.push(Problem::Timestamp { row, timestamp });
+    }
+    if !is_magnet(&torrent.magnet_url) {
+        problems.push(Problem::Magnet {
+            row,
+            magnet: torrent.magnet_url.clone(),
+        });
+    }
+    problems
+}
+
+/// `magnet:?xt=urn:btih:` with a hex or base32 info hash
+fn is_magnet(url: &str) -> bool {
+    let Some(params) = url.strip_prefix("magnet:?") else {
+        return false;
+    };
+    params.split('&').any(|param| {
+        let Some(hash) = param.strip_prefix("xt=urn:btih:") else {
+            return false;
+        };
+        match hash.len() {
+            40 => hash.chars().all(|c| c.is_ascii_hexdigit()),
+            32 => hash.chars().all(
+                |c| matches!(c.to_ascii_uppercase(), 'A'..='Z' | '2'..='7'),
+            ),
+            _ => false,
+        }
+    })
+}
+
+/// Fetch a search and check its page.
+pub(crate) async fn check<C: Category, T: Transport>(
+    transport: &T,
+    extractor: &Extractor,
+    base_url: &str,
+    query: &Query<C>,
+) -> Result<HealthReport> {
+    let url = format!("{}/?{}", base_url, query);
+
+    event!(Level::DEBUG, "url = {}", url);
+
+    let res = transport
+        .send(transport::request(Method::GET, &url).empty()?)
+        .await?;
+    Ok(report(url, res.status(), &res.text(), extractor, base_url))
+}
+
+/// Check the page fetched from `url`, including its status.
+pub(crate) fn report(
+    url: String,
+    status: StatusCode,
+    html: &str,
+    extractor: &Extractor,
+    base_url: &str,
+) -> HealthReport {
+    let mut report = check_page(html, extractor, base_url);
+    if !status.is_success() {
+        report.problems.insert(0, Problem::Status(status.as_u16()));
+    }
+    report.url = url;
+    report.status = status.as_u16();
+
+    if !report.is_healthy() {
+        event!(Level::WARN, "layout drift: {:?}", report.problems);
+    }
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::extractor::ExtractorConfig;
+    use crate::mock;
+    use crate::renderer::render;
+    use crate::testing::FakeNyaa;
+    use crate::{NyaaClient, QueryBuilder};
+
+    fn torrent(id: u32) -> Torrent {
+        mock::torrent(id).build()
+    }
+
+    #[test]
+    fn healthy_page() {
+        let html = render(&[torrent(1), torrent(2)], "https://nyaa.si");
+        let report = check_page(&html, &Extractor::default(), "");
+        assert!(report.is_healthy(), "{:?}", report.problems);
+        assert_eq!((report.rows, report.parsed), (2, 2));
+    }
+
+    #[test]
+    fn report_drift() {
+        let extractor = Extractor::default();
+        let mut bad = torrent(2);
+        bad.magnet_url = "magnet:?xt=urn:sha1:abc".into();
+        bad.date = chrono::DateTime::from_timestamp(0, 0).unwrap();
+        let html = render(&[torrent(1), bad], "https://nyaa.si")
+            .replace("hdr-size", "hdr-bytes")
+            .replacen("1.2 GiB", "1.2 GB", 1);
+
+        let report = check_page(&html, &extractor, "");
+        assert_eq!(
+            report.problems,
+            [
+                Problem::MissingHeader("hdr-size".into()),
+                Problem::Row {
+                    row: 0,
+                    error: "Size parsing error: Invalid size: 1.2 GB".into()
+                },
+                Problem::Timestamp {
+                    row: 1,
+                    timestamp: 0
+                },
+                Problem::Magnet {
+                    row: 1,
+                    magnet: "magnet:?xt=urn:sha1:abc".into()
+                },
+            ]
+        );
+        assert_eq!((report.rows, report.parsed), (2, 1));
+
+        let mut config = ExtractorConfig::default();
+        config.columns.size = 5;
+        config.columns.date = 4;
+        let swapped = config.build().unwrap();
+        let html = render(&[torrent(1)], "https://nyaa.si");
+        let report = check_page(&html, &swapped, "");
+        assert_eq!(
+            report.problems[..2],
+            [
+                Problem::MissingHeader("hdr-date".into()),
+                Problem::MissingHeader("hdr-size".into()),
+            ]
+        );
+
+        let report = check_page("<h3>No results found</h3>", &extractor, "");
+        assert_eq!(report.problems[0], Problem::MissingTable);
+        assert_eq!(report.problems.last(), Some(&Problem::NoRows));
+    }
+
+    #[tokio::test]
+    async fn check_live_layout() {
+        let server = FakeNyaa::new().torrent(torrent(1)).start().unwrap();
+        let client = NyaaClient::with_transport(server.transport());
+
+        let query = QueryBuilder::new().search("show").build();
+        let report = client.check_layout(&query).await.unwrap();
+        assert!(report.is_healthy(), "{:?}", report.problems);
+        assert_eq!(report.url, format!("https://nyaa.si/?{}", query));
+
+        let query = QueryBuilder::new().search("missing").build();
+        let report = client.check_layout(&query).await.unwrap();
+        assert!(report.problems.contains(&Problem::NoRows));
+    }
+}
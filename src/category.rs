@@ -1,7 +1,185 @@
-use std::fmt::Display;
+use std::fmt::{Debug, Display};
 use std::hash::Hash;
+use std::str::FromStr;
 
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::error::{Error, Result};
+
+/// A category of a site, shown and serialized as its code, e.g. `1_2`
+///
+/// Parsing accepts the code, the name or the variant name, ignoring case
+/// and punctuation, so `1_4`, `Anime - Raw` and `anime-raw` are the same.
 pub trait Category:
-    Copy + Clone + Display + PartialEq + Eq + Hash + Default
+    Copy
+    + Clone
+    + Debug
+    + Display
+    + PartialEq
+    + Eq
+    + Hash
+    + Default
+    + FromStr<Err = Error>
+    + Serialize
+    + DeserializeOwned
+    + Send
+    + Sync
+    + 'static
 {
+    /// Every category, starting with the default `All`
+    fn all() -> Vec<Self>;
+
+    /// Code used in urls and forms, e.g. `1_2`
+    fn code(&self) -> &'static str;
+
+    /// Name as shown on the site, e.g. `Anime - English-translated`
+    fn name(&self) -> &'static str;
+
+    fn description(&self) -> &'static str;
+
+    /// Whether this is a main category like `1_0`, rather than `All` or a
+    /// sub-category
+    fn is_main(&self) -> bool {
+        let (main, sub) = split_code(self.code());
+        main != "0" && sub == "0"
+    }
+
+    /// `All` for main categories and the main category for sub-categories
+    fn parent(&self) -> Option<Self> {
+        let (main, sub) = split_code(self.code());
+        let parent = match (main, sub) {
+            ("0", _) => return None,
+            (_, "0") => "0_0".to_string(),
+            (main, _) => format!("{}_0", main),
+        };
+        Self::all()
+            .into_iter()
+            .find(|category| category.code() == parent)
+    }
+
+    /// The main categories for `All` and the sub-categories for a main one
+    fn children(&self) -> Vec<Self> {
+        Self::all()
+            .into_iter()
+            .filter(|category| category.parent().as_ref() == Some(self))
+            .collect()
+    }
+
+    /// Whether `other` is this category or falls under it
+    fn contains(&self, other: &Self) -> bool {
+        self == other
+            || other.parent().is_some_and(|parent| self.contains(&parent))
+    }
+}
+
+/// `("1", "2")` for `1_2`
+pub(crate) fn split_code(code: &str) -> (&str, &str) {
+    code.split_once('_').unwrap_or((code, "0"))
+}
+
+/// `main_sub` with both parts numeric, like every NyaaV2 category code
+pub(crate) fn is_code(s: &str) -> bool {
+    let (main, sub) = split_code(s);
+    s.contains('_')
+        && [main, sub].iter().all(|part| {
+            !part.is_empty() && part.chars().all(|c| c.is_ascii_digit())
+        })
+}
+
+/// Find the category matching `s` by code, name or variant name.
+pub(crate) fn parse<C: Category>(s: &str) -> Result<C> {
+    let wanted = normalize(s);
+    C::all()
+        .into_iter()
+        .find(|category| {
+            category.code() == s.trim()
+                || normalize(category.name()) == wanted
+                || normalize(&format!("{:?}", category)) == wanted
+        })
+        .ok_or_else(|| Error::CategoryError(format!("Unknown category {}", s)))
+}
+
+/// Lowercase alphanumerics only, to compare names loosely
+pub(crate) fn normalize(s: &str) -> String {
+    s.chars()
+        .filter(char::is_ascii_alphanumeric)
+        .map(|c| c.to_ascii_lowercase())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{NyaaCategory, SukebeiCategory};
+
+    fn check_table<C: Category>() {
+        let all = C::all();
+        assert_eq!(all[0], C::default());
+        for (i, category) in all.iter().enumerate() {
+            assert_eq!(category.to_string(), category.code());
+            assert_eq!(category.code().parse::<C>().unwrap(), *category);
+            assert_eq!(category.name().parse::<C>().unwrap(), *category);
+            assert!(all[..i].iter().all(|other| other != category));
+        }
+    }
+
+    #[test]
+    fn category_tables() {
+        check_table::<NyaaCategory>();
+        check_table::<SukebeiCategory>();
+    }
+
+    #[test]
+    fn parse_category() {
+        for s in ["1_4", "Anime - Raw", "anime-raw", "AnimeRaw"] {
+            assert_eq!(
+                s.parse::<NyaaCategory>().unwrap(),
+                NyaaCategory::AnimeRaw
+            );
+        }
+        assert_eq!(
+            "Real Life - Photobooks and Pictures"
+                .parse::<SukebeiCategory>()
+                .unwrap(),
+            SukebeiCategory::RealLifePhotobooks
+        );
+        let err = "7_0".parse::<NyaaCategory>().unwrap_err();
+        assert_eq!(err.to_string(), "Category error: Unknown category 7_0");
+    }
+
+    #[test]
+    fn category_hierarchy() {
+        use NyaaCategory::*;
+
+        assert!(Anime.is_main() && !All.is_main() && !AnimeRaw.is_main());
+        assert_eq!(All.parent(), None);
+        assert_eq!(Audio.parent(), Some(All));
+        assert_eq!(AudioLossy.parent(), Some(Audio));
+        assert_eq!(
+            Literature.children(),
+            [
+                LiteratureEnglishTranslated,
+                LiteratureNonEnglishTranslated,
+                LiteratureRaw
+            ]
+        );
+        assert_eq!(All.children().len(), 6);
+        assert!(AnimeRaw.children().is_empty());
+
+        assert!(All.contains(&AnimeRaw));
+        assert!(Anime.contains(&AnimeRaw));
+        assert!(AnimeRaw.contains(&AnimeRaw));
+        assert!(!Anime.contains(&AudioLossy));
+        assert!(!AnimeRaw.contains(&Anime));
+        assert!(SukebeiCategory::Art.contains(&SukebeiCategory::ArtManga));
+    }
+
+    #[test]
+    fn serde_uses_code() {
+        let json = serde_json::to_string(&NyaaCategory::AudioLossless).unwrap();
+        assert_eq!(json, "\"2_1\"");
+        let category: NyaaCategory = serde_json::from_str(&json).unwrap();
+        assert_eq!(category, NyaaCategory::AudioLossless);
+        assert!(serde_json::from_str::<NyaaCategory>("\"9_9\"").is_err());
+    }
 }
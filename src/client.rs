@@ -1,14 +1,21 @@
+use std::future::Future;
+use std::pin::Pin;
+
+use crate::any::{AnyCategory, AnySite};
 use crate::category::Category;
 use crate::error::Result;
 use crate::model::Torrent;
 use crate::query::Query;
+use crate::transport::Transport;
+
+pub type BoxFuture<'a, T> = Pin<Box<dyn Future<Output = T> + Send + 'a>>;
 
 pub trait Client<C: Category> {
     const BASE_URL: &'static str;
 
     /// Send a query to the api
     ///
-    ///```
+    ///```no_run
     #[doc = include_str!("../examples/custom_query.rs")]
     ///```
     fn get(
@@ -16,3 +23,104 @@ pub trait Client<C: Category> {
         query: &Query<C>,
     ) -> impl std::future::Future<Output = Result<Vec<Torrent>>> + Send;
 }
+
+/// An object-safe [`Client`], for `dyn DynClient` backends
+///
+/// Every [`Client`] implements it, as does [`AnySite`], which also covers
+/// custom instances.
+pub trait DynClient<C: Category = AnyCategory>: Send + Sync {
+    fn base_url(&self) -> &str;
+
+    /// Send a query to the api
+    fn get<'a>(
+        &'a self,
+        query: &'a Query<C>,
+    ) -> BoxFuture<'a, Result<Vec<Torrent>>>;
+}
+
+impl<C: Category, T: Client<C> + Send + Sync> DynClient<C> for T {
+    fn base_url(&self) -> &str {
+        T::BASE_URL
+    }
+
+    fn get<'a>(
+        &'a self,
+        query: &'a Query<C>,
+    ) -> BoxFuture<'a, Result<Vec<Torrent>>> {
+        Box::pin(Client::get(self, query))
+    }
+}
+
+impl<T: Transport> DynClient<AnyCategory> for AnySite<T> {
+    fn base_url(&self) -> &str {
+        AnySite::base_url(self)
+    }
+
+    fn get<'a>(
+        &'a self,
+        query: &'a Query<AnyCategory>,
+    ) -> BoxFuture<'a, Result<Vec<Torrent>>> {
+        Box::pin(AnySite::get(self, query))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::mock::torrent;
+    use crate::testing::FakeNyaa;
+    use crate::{NyaaCategory, NyaaClient, QueryBuilder, SukebeiCategory};
+
+    /// A backend that always returns one torrent
+    struct StubClient;
+
+    impl DynClient<NyaaCategory> for StubClient {
+        fn base_url(&self) -> &str {
+            "stub://"
+        }
+
+        fn get<'a>(
+            &'a self,
+            _query: &'a Query<NyaaCategory>,
+        ) -> BoxFuture<'a, Result<Vec<Torrent>>> {
+            Box::pin(async { Ok(vec![torrent(1).title("stub").build()]) })
+        }
+    }
+
+    #[tokio::test]
+    async fn dyn_backends() {
+        let server = FakeNyaa::new().start().unwrap();
+        let backends: Vec<Arc<dyn DynClient<NyaaCategory>>> = vec![
+            Arc::new(NyaaClient::with_transport(server.transport())),
+            Arc::new(StubClient),
+        ];
+        let query = QueryBuilder::new().build();
+
+        let mut found = Vec::new();
+        for backend in &backends {
+            found
+                .push((backend.base_url(), backend.get(&query).await.unwrap()));
+        }
+        assert_eq!(found[0].0, "https://nyaa.si");
+        assert!(found[0].1.is_empty());
+        assert_eq!(found[1].1[0].title, "stub");
+    }
+
+    #[tokio::test]
+    async fn dyn_any_sites() {
+        let server = FakeNyaa::new().start().unwrap();
+        let sites: Vec<Arc<dyn DynClient>> = vec![
+            Arc::new(AnySite::with_transport("nyaa", server.transport())),
+            Arc::new(AnySite::with_transport("sukebei", server.transport())),
+        ];
+        let query = QueryBuilder::new()
+            .category(SukebeiCategory::ArtManga.into())
+            .build();
+
+        assert!(sites[0].get(&query).await.is_err());
+        assert!(sites[1].get(&query).await.unwrap().is_empty());
+        assert_eq!(sites[1].base_url(), "https://sukebei.nyaa.si");
+    }
+}
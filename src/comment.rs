@@ -0,0 +1,231 @@
+use serde::Deserialize;
+use tracing::{event, Level};
+
+use crate::{
+    category::Category,
+    error::{Error, Result},
+    form::{csrf_token, error_message},
+    model::{CommentId, TorrentId},
+    session::{access_error, Session},
+    transport::{RequestBuilderExt, ResponseExt, Transport},
+};
+
+/// Reply of the comment edit endpoint
+#[derive(Debug, Deserialize)]
+struct EditCommentResponse {
+    #[serde(default)]
+    error: Vec<String>,
+}
+
+impl<C: Category, T: Transport> Session<C, T> {
+    /// Post a comment on `/view/{id}`
+    #[tracing::instrument(skip(self, text))]
+    pub async fn post_comment(&self, id: TorrentId, text: &str) -> Result<()> {
+        let path = format!("/view/{}", id);
+        let token = self.page_csrf_token(&path, id).await?;
+        self.submit(&path, &[("csrf_token", &token), ("comment", text)], id)
+            .await
+    }
+
+    /// Replace the text of one of the session user's comments
+    #[tracing::instrument(skip(self, text))]
+    pub async fn edit_comment(
+        &self,
+        id: TorrentId,
+        comment: CommentId,
+        text: &str,
+    ) -> Result<()> {
+        let token = self.page_csrf_token(&format!("/view/{}", id), id).await?;
+        let path = format!("/view/{}/comment/{}/edit", id, comment);
+        let res = self
+            .send_raw(
+                self.post(&path).form(&[
+                    ("csrf_token", token.as_str()),
+                    ("comment", text),
+                ])?,
+            )
+            .await?;
+
+        let status = res.status();
+        if status.is_success() {
+            return Ok(());
+        }
+        if status != http::StatusCode::BAD_REQUEST {
+            return Err(comment_error(status.as_u16(), comment));
+        }
+        let reply: EditCommentResponse = serde_json::from_slice(res.body())?;
+        Err(Error::FormError(reply.error.join(", ")))
+    }
+
+    /// Delete one of the session user's comments
+    #[tracing::instrument(skip(self))]
+    pub async fn delete_comment(
+        &self,
+        id: TorrentId,
+        comment: CommentId,
+    ) -> Result<()> {
+        let token = self.page_csrf_token(&format!("/view/{}", id), id).await?;
+        let path = format!("/view/{}/comment/{}/delete", id, comment);
+        self.send(self.post(&path).form(&[("csrf_token", token.as_str())])?)
+            .await
+            .map_err(|err| match err {
+                Error::StatusError(status) => comment_error(status, comment),
+                err => err,
+            })?;
+        Ok(())
+    }
+
+    /// Report a torrent to the moderators
+    #[tracing::instrument(skip(self))]
+    pub async fn report_torrent(
+        &self,
+        id: TorrentId,
+        reason: &str,
+    ) -> Result<()> {
+        let token = self.page_csrf_token(&format!("/view/{}", id), id).await?;
+        let path = format!("/view/{}/submit_report", id);
+        self.submit(&path, &[("csrf_token", &token), ("reason", reason)], id)
+            .await
+    }
+
+    async fn page_csrf_token(
+        &self,
+        path: &str,
+        id: TorrentId,
+    ) -> Result<String> {
+        let page = self
+            .fetch(path)
+            .await
+            .map_err(|err| access_error(err, &format!("torrent {}", id)))?;
+        csrf_token(&page)
+    }
+
+    /// Post a form that redirects back to the torrent page on success.
+    async fn submit(
+        &self,
+        path: &str,
+        form: &[(&str, &str)],
+        id: TorrentId,
+    ) -> Result<()> {
+        let res = self
+            .send(self.post(path).form(form)?)
+            .await
+            .map_err(|err| access_error(err, &format!("torrent {}", id)))?;
+
+        if res.status().is_redirection() {
+            event!(Level::DEBUG, "submitted {}", path);
+            return Ok(());
+        }
+        let page = res.text();
+        Err(Error::FormError(
+            error_message(&page).unwrap_or_else(|| "Submit failed".into()),
+        ))
+    }
+}
+
+fn comment_error(status: u16, comment: CommentId) -> Error {
+    access_error(Error::StatusError(status), &format!("comment {}", comment))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::{MockResponse, MockServer};
+    use crate::NyaaCategory;
+
+    const VIEW_PAGE: &str = r#"
+        <form class="search-bar" action="/" method="get">
+            <input name="q" type="text">
+        </form>
+        <form class="comment-box" method="POST">
+            <input id="csrf_token" name="csrf_token" type="hidden" value="cm">
+            <textarea name="comment"></textarea>
+        </form>"#;
+
+    fn comment_server() -> MockServer {
+        MockServer::start(|req| {
+            if req.method == "GET" {
+                return match req.path.as_str() {
+                    "/view/1" => MockResponse::html(VIEW_PAGE),
+                    _ => MockResponse::status(404),
+                };
+            }
+            let form = req.form();
+            assert_eq!(form["csrf_token"], "cm");
+            match req.path.as_str() {
+                "/view/1" if form["comment"].is_empty() => MockResponse::html(
+                    r#"<div class="alert alert-danger">Comment required</div>"#,
+                ),
+                "/view/1" => MockResponse::redirect("/view/1#com-1"),
+                "/view/1/comment/7/edit" if form["comment"].is_empty() => {
+                    MockResponse::html(r#"{"error": ["Comment required"]}"#)
+                        .with_status(400)
+                }
+                "/view/1/comment/7/edit" => {
+                    MockResponse::html(r#"{"comment": "edited"}"#)
+                }
+                "/view/1/comment/7/delete" => MockResponse::redirect("/view/1"),
+                "/view/1/comment/8/delete" => MockResponse::status(403),
+                "/view/1/submit_report" => {
+                    assert_eq!(form["reason"], "Fake upload");
+                    MockResponse::redirect("/view/1")
+                }
+                _ => MockResponse::status(404),
+            }
+        })
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn post_and_edit_comments() {
+        let server = comment_server();
+        let session = Session::<NyaaCategory>::new(server.url());
+        let id = TorrentId(1);
+
+        session.post_comment(id, "Thanks!").await.unwrap();
+        let err = session.post_comment(id, "").await.unwrap_err();
+        assert_eq!(err.to_string(), "Form error: Comment required");
+
+        session
+            .edit_comment(id, CommentId(7), "edited")
+            .await
+            .unwrap();
+        let err = session
+            .edit_comment(id, CommentId(7), "")
+            .await
+            .unwrap_err();
+        assert_eq!(err.to_string(), "Form error: Comment required");
+    }
+
+    #[tokio::test]
+    async fn delete_comments() {
+        let server = comment_server();
+        let session = Session::<NyaaCategory>::new(server.url());
+
+        session
+            .delete_comment(TorrentId(1), CommentId(7))
+            .await
+            .unwrap();
+        let err = session
+            .delete_comment(TorrentId(1), CommentId(8))
+            .await
+            .unwrap_err();
+        assert!(matches!(err, Error::PermissionError(_)));
+    }
+
+    #[tokio::test]
+    async fn report_torrent() {
+        let server = comment_server();
+        let session = Session::<NyaaCategory>::new(server.url());
+
+        session
+            .report_torrent(TorrentId(1), "Fake upload")
+            .await
+            .unwrap();
+        let err = session
+            .report_torrent(TorrentId(2), "Fake upload")
+            .await
+            .unwrap_err();
+        assert!(matches!(err, Error::NotFoundError(_)));
+    }
+}
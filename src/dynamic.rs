@@ -0,0 +1,304 @@
+//! Categories discovered at runtime, for NyaaV2 instances other than nyaa.si
+//! and sukebei.nyaa.si.
+//!
+//! ```no_run
+//! use nyaa_si::{dynamic::DynamicCategory, QueryBuilder, Session};
+//!
+//! # async fn run() -> nyaa_si::error::Result<()> {
+//! let session = Session::<DynamicCategory>::new("https://nyaa.example.org");
+//! let categories = session.discover_categories().await?;
+//! let anime = categories.parse("Anime")?;
+//! let query = QueryBuilder::new().category(anime).build();
+//! let torrents = session.get(&query).await?;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::collections::HashMap;
+use std::fmt::Display;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex, OnceLock};
+
+use scraper::{Html, Selector};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::{
+    category::{self, is_code, split_code, Category},
+    error::{Error, Result},
+    session::Session,
+    transport::Transport,
+};
+
+static OPTION_SELECTOR: OnceLock<Selector> = OnceLock::new();
+
+/// Codes interned so far, bounded by both parts fitting a `u8`
+static CODES: Mutex<Vec<&'static str>> = Mutex::new(Vec::new());
+
+/// A category of a NyaaV2 instance, known only by its code
+///
+/// Only the code is kept, interned so categories stay `Copy` like the
+/// compile-time enums, and codes are limited to parts up to 255 so that
+/// interning is bounded. Names belong to the site and live in the
+/// [`CategorySet`] it was discovered from.
+#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq)]
+pub struct DynamicCategory {
+    code: &'static str,
+}
+
+impl DynamicCategory {
+    /// The category with `code`, e.g. `1_2`
+    pub fn new(code: &str) -> Result<Self> {
+        let (main, sub) = split_code(code.trim());
+        let parts = (main.parse::<u8>(), sub.parse::<u8>());
+        let (true, (Ok(main), Ok(sub))) = (is_code(code.trim()), parts) else {
+            return Err(Error::CategoryError(format!(
+                "Invalid category code {}",
+                code
+            )));
+        };
+        // `01_2` and `1_2` are the same category
+        let code = format!("{}_{}", main, sub);
+
+        let mut codes = CODES.lock().unwrap();
+        if let Some(interned) = codes.iter().find(|interned| **interned == code)
+        {
+            return Ok(Self { code: interned });
+        }
+        let code: &'static str = Box::leak(code.into_boxed_str());
+        codes.push(code);
+        Ok(Self { code })
+    }
+}
+
+impl Default for DynamicCategory {
+    fn default() -> Self {
+        Self { code: "0_0" }
+    }
+}
+
+impl Display for DynamicCategory {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.code)
+    }
+}
+
+impl FromStr for DynamicCategory {
+    type Err = Error;
+
+    /// Parse a category code, names need the site's [`CategorySet`]
+    fn from_str(s: &str) -> Result<Self> {
+        Self::new(s)
+    }
+}
+
+impl Serialize for DynamicCategory {
+    fn serialize<S: Serializer>(
+        &self,
+        serializer: S,
+    ) -> std::result::Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.code)
+    }
+}
+
+impl<'de> Deserialize<'de> for DynamicCategory {
+    fn deserialize<D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> std::result::Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        s.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+impl Category for DynamicCategory {
+    /// Only `All`, as the other categories depend on the site, see
+    /// [`CategorySet::all`]
+    fn all() -> Vec<Self> {
+        vec![Self::default()]
+    }
+
+    fn code(&self) -> &'static str {
+        self.code
+    }
+
+    /// The code, as names depend on the site, see [`CategorySet::name`]
+    fn name(&self) -> &'static str {
+        self.code
+    }
+
+    /// Search forms carry no descriptions, so this is always empty
+    fn description(&self) -> &'static str {
+        ""
+    }
+
+    fn parent(&self) -> Option<Self> {
+        match split_code(self.code) {
+            ("0", _) => None,
+            (_, "0") => Some(Self::default()),
+            (main, _) => Self::new(&format!("{}_0", main)).ok(),
+        }
+    }
+}
+
+/// The categories of one site, with their names
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CategorySet {
+    categories: Vec<DynamicCategory>,
+    names: HashMap<DynamicCategory, Arc<str>>,
+}
+
+impl CategorySet {
+    /// Read the options of the `<select name="c">` in a page's search form.
+    pub fn from_search_form(html: &str) -> Result<Self> {
+        let document = Html::parse_document(html);
+        let selector = OPTION_SELECTOR.get_or_init(|| {
+            Selector::parse(r#"select[name="c"] option"#).unwrap()
+        });
+        let mut set = Self::default();
+        for option in document.select(selector) {
+            let Some(code) = option.value().attr("value") else {
+                continue;
+            };
+            let Ok(category) = DynamicCategory::new(code) else {
+                continue;
+            };
+            let text: String = option.text().collect();
+            // sub-categories without a title read like `- Comics`
+            let name = match option.value().attr("title") {
+                Some(title) => title.trim(),
+                None => text.trim().trim_start_matches(['-', ' ']),
+            };
+            set.insert(category, name);
+        }
+        if set.categories.is_empty() {
+            return Err(Error::SelectorError("Categories not found".into()));
+        }
+        Ok(set)
+    }
+
+    /// Add or rename a category
+    pub fn insert(&mut self, category: DynamicCategory, name: &str) {
+        if !self.names.contains_key(&category) {
+            self.categories.push(category);
+        }
+        self.names.insert(category, name.into());
+    }
+
+    /// Every category in the order of the search form
+    pub fn all(&self) -> &[DynamicCategory] {
+        &self.categories
+    }
+
+    pub fn len(&self) -> usize {
+        self.categories.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.categories.is_empty()
+    }
+
+    pub fn name(&self, category: &DynamicCategory) -> Option<&str> {
+        self.names.get(category).map(|name| &**name)
+    }
+
+    /// Look a category up by code or name, ignoring case and punctuation
+    pub fn parse(&self, s: &str) -> Result<DynamicCategory> {
+        self.categories
+            .iter()
+            .find(|category| {
+                category.code() == s.trim()
+                    || self.name(category).is_some_and(|name| {
+                        category::normalize(name) == category::normalize(s)
+                    })
+            })
+            .copied()
+            .ok_or_else(|| {
+                Error::CategoryError(format!("Unknown category {}", s))
+            })
+    }
+
+    /// The main categories for `All` and the sub-categories for a main one
+    pub fn children(&self, category: &DynamicCategory) -> Vec<DynamicCategory> {
+        self.categories
+            .iter()
+            .filter(|child| child.parent().as_ref() == Some(category))
+            .copied()
+            .collect()
+    }
+}
+
+impl<T: Transport> Session<DynamicCategory, T> {
+    /// Scrape the categories of the site's search form
+    pub async fn discover_categories(&self) -> Result<CategorySet> {
+        CategorySet::from_search_form(&self.fetch("/").await?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::{MockResponse, MockServer, LISTING};
+    use crate::QueryBuilder;
+
+    const SEARCH_FORM: &str = r#"
+        <form class="navbar-form" action="/" method="get">
+            <input name="q" type="text">
+            <select class="form-control" title="Category" name="c">
+                <option value="0_0" title="All categories">All categories</option>
+                <option value="1_0" title="Books">Books</option>
+                <option value="1_1" title="Books - Comics">- Comics</option>
+                <option value="1_2">- Novels</option>
+                <option value="2_0" title="Music">Music</option>
+            </select>
+        </form>"#;
+
+    #[test]
+    fn parse_search_form() {
+        let categories = CategorySet::from_search_form(SEARCH_FORM).unwrap();
+        assert_eq!(categories.len(), 5);
+        assert_eq!(categories.all()[0], DynamicCategory::default());
+
+        let books = categories.parse("books").unwrap();
+        assert_eq!(books.code(), "1_0");
+        assert_eq!(categories.name(&books), Some("Books"));
+        assert!(books.is_main());
+        assert_eq!(categories.children(&books), categories.all()[2..4]);
+        assert_eq!(categories.name(&categories.all()[3]), Some("Novels"));
+        assert!(books.contains(&categories.parse("Books - Comics").unwrap()));
+        assert!(!books.contains(&"2_0".parse().unwrap()));
+        assert!(categories.parse("comics and novels").is_err());
+        assert!(CategorySet::from_search_form(LISTING).is_err());
+    }
+
+    #[test]
+    fn parse_codes() {
+        let unknown: DynamicCategory = "9_3".parse().unwrap();
+        assert_eq!(unknown.parent().unwrap().code(), "9_0");
+        assert_eq!("09_3".parse::<DynamicCategory>().unwrap(), unknown);
+        assert!("9_300".parse::<DynamicCategory>().is_err());
+        assert!("books".parse::<DynamicCategory>().is_err());
+        assert!("9".parse::<DynamicCategory>().is_err());
+        // no names are shared between sites
+        assert_eq!(unknown.name(), "9_3");
+        assert_eq!(DynamicCategory::all(), [DynamicCategory::default()]);
+    }
+
+    #[tokio::test]
+    async fn search_with_discovered_categories() {
+        let server = MockServer::start(|req| match req.route() {
+            "/" if req.query().is_empty() => MockResponse::html(SEARCH_FORM),
+            "/" => {
+                assert_eq!(req.query()["c"], "1_1");
+                MockResponse::html(LISTING)
+            }
+            _ => MockResponse::status(404),
+        })
+        .unwrap();
+        let session = Session::<DynamicCategory>::new(server.url());
+
+        let categories = session.discover_categories().await.unwrap();
+        let comics = categories.parse("Books - Comics").unwrap();
+        let query = QueryBuilder::new().category(comics).build();
+        let torrents = session.get(&query).await.unwrap();
+        assert_eq!(torrents[0].title, "[Group] Show - 01");
+    }
+}
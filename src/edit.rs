@@ -0,0 +1,263 @@
+use tracing::{event, Level};
+
+use crate::{
+    category::Category,
+    error::{Error, Result},
+    form::{error_message, HtmlForm},
+    model::TorrentId,
+    session::{access_error, Session},
+    transport::{RequestBuilderExt, ResponseExt, Transport},
+};
+
+/// Changes to a torrent's details, fields left unset keep their current value
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct TorrentChanges<C> {
+    name: Option<String>,
+    category: Option<C>,
+    information: Option<String>,
+    description: Option<String>,
+    anonymous: Option<bool>,
+    hidden: Option<bool>,
+    remake: Option<bool>,
+    complete: Option<bool>,
+}
+
+impl<C: Category> TorrentChanges<C> {
+    pub fn new() -> TorrentChanges<C> {
+        TorrentChanges::default()
+    }
+
+    pub fn name<S: Into<String>>(mut self, name: S) -> Self {
+        self.name = Some(name.into());
+        self
+    }
+
+    pub fn category(mut self, category: C) -> Self {
+        self.category = Some(category);
+        self
+    }
+
+    pub fn information<S: Into<String>>(mut self, information: S) -> Self {
+        self.information = Some(information.into());
+        self
+    }
+
+    pub fn description<S: Into<String>>(mut self, description: S) -> Self {
+        self.description = Some(description.into());
+        self
+    }
+
+    pub fn anonymous(mut self, anonymous: bool) -> Self {
+        self.anonymous = Some(anonymous);
+        self
+    }
+
+    pub fn hidden(mut self, hidden: bool) -> Self {
+        self.hidden = Some(hidden);
+        self
+    }
+
+    pub fn remake(mut self, remake: bool) -> Self {
+        self.remake = Some(remake);
+        self
+    }
+
+    pub fn complete(mut self, complete: bool) -> Self {
+        self.complete = Some(complete);
+        self
+    }
+
+    fn apply(&self, form: &mut HtmlForm) {
+        if let Some(name) = &self.name {
+            form.set("display_name", name.as_str());
+        }
+        if let Some(category) = &self.category {
+            form.set("category", category.to_string());
+        }
+        if let Some(information) = &self.information {
+            form.set("information", information.as_str());
+        }
+        if let Some(description) = &self.description {
+            form.set("description", description.as_str());
+        }
+        for (field, flag) in [
+            ("is_anonymous", self.anonymous),
+            ("is_hidden", self.hidden),
+            ("is_remake", self.remake),
+            ("is_complete", self.complete),
+        ] {
+            if let Some(checked) = flag {
+                form.check(field, checked);
+            }
+        }
+    }
+}
+
+impl<C: Category, T: Transport> Session<C, T> {
+    /// Edit one of the session user's torrents
+    #[tracing::instrument(skip(self, changes))]
+    pub async fn edit_torrent(
+        &self,
+        id: TorrentId,
+        changes: &TorrentChanges<C>,
+    ) -> Result<()> {
+        let mut form = self.edit_form(id).await?;
+        changes.apply(&mut form);
+        form.set("submit", "Save Changes");
+        self.submit_edit_form(id, &form).await
+    }
+
+    /// Delete one of the session user's torrents, through the delete form
+    /// below the edit form
+    #[tracing::instrument(skip(self))]
+    pub async fn delete_torrent(&self, id: TorrentId) -> Result<()> {
+        let page = self.edit_page(id).await?;
+        let mut form = HtmlForm::find(&page, "delete")?;
+        form.set("delete", "Delete");
+
+        let res = self
+            .send(
+                self.post(&format!("/view/{}/delete", id))
+                    .form(form.fields())?,
+            )
+            .await
+            .map_err(|err| access_error(err, &format!("torrent {}", id)))?;
+
+        if res.status().is_redirection() {
+            event!(Level::DEBUG, "deleted torrent {}", id);
+            return Ok(());
+        }
+        let page = res.text();
+        Err(Error::FormError(
+            error_message(&page).unwrap_or_else(|| "Delete failed".into()),
+        ))
+    }
+
+    async fn edit_page(&self, id: TorrentId) -> Result<String> {
+        self.fetch(&format!("/view/{}/edit", id))
+            .await
+            .map_err(|err| access_error(err, &format!("torrent {}", id)))
+    }
+
+    async fn edit_form(&self, id: TorrentId) -> Result<HtmlForm> {
+        let page = self.edit_page(id).await?;
+        HtmlForm::find(&page, "display_name")
+    }
+
+    async fn submit_edit_form(
+        &self,
+        id: TorrentId,
+        form: &HtmlForm,
+    ) -> Result<()> {
+        let res = self
+            .send(
+                self.post(&format!("/view/{}/edit", id))
+                    .form(form.fields())?,
+            )
+            .await
+            .map_err(|err| access_error(err, &format!("torrent {}", id)))?;
+
+        if res.status().is_redirection() {
+            event!(Level::DEBUG, "edited torrent {}", id);
+            return Ok(());
+        }
+        let page = res.text();
+        Err(Error::FormError(
+            error_message(&page).unwrap_or_else(|| "Edit failed".into()),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::{MockResponse, MockServer};
+    use crate::NyaaCategory;
+
+    const EDIT_PAGE: &str = r#"<nav class="navbar">
+    <form class="navbar-form navbar-right form" action="/" method="get">
+        <input type="text" class="form-control search-bar" name="q" value="">
+    </form>
+    </nav>
+    <form method="POST" enctype="multipart/form-data">
+        <input id="csrf_token" name="csrf_token" type="hidden" value="ed">
+        <input name="display_name" type="text" value="[Group] Show - 01 (typo)">
+        <select name="category">
+            <option value="1_2" selected>Anime - English-translated</option>
+            <option value="1_4">Anime - Raw</option>
+        </select>
+        <input name="information" type="text" value="https://example.org">
+        <textarea name="description">Old description</textarea>
+        <input name="is_hidden" type="checkbox" value="y" checked>
+        <input name="is_complete" type="checkbox" value="y">
+        <input name="submit" type="submit" value="Save Changes">
+    </form>
+    <div class="panel panel-danger">
+        <form method="POST" action="/view/1/delete">
+            <input id="csrf_token" name="csrf_token" type="hidden" value="ed">
+            <input name="delete" type="submit" value="Delete">
+        </form>
+    </div>"#;
+
+    fn edit_server() -> MockServer {
+        MockServer::start(|req| {
+            match (req.method.as_str(), req.path.as_str()) {
+                ("GET", "/view/1/edit") => MockResponse::html(EDIT_PAGE),
+                ("POST", "/view/1/edit") => {
+                    let form = req.form();
+                    assert_eq!(form["csrf_token"], "ed");
+                    assert!(!form.contains_key("delete"));
+                    assert_eq!(form["submit"], "Save Changes");
+                    assert_eq!(form["display_name"], "[Group] Show - 01");
+                    assert_eq!(form["category"], "1_2");
+                    assert_eq!(form["information"], "https://example.org");
+                    assert_eq!(form["description"], "Old description");
+                    assert_eq!(form["is_complete"], "y");
+                    assert!(!form.contains_key("is_hidden"));
+                    MockResponse::redirect("/view/1")
+                }
+                ("POST", "/view/1/delete") => {
+                    let form = req.form();
+                    assert_eq!(form["csrf_token"], "ed");
+                    assert_eq!(form["delete"], "Delete");
+                    assert!(!form.contains_key("display_name"));
+                    MockResponse::redirect("/")
+                }
+                ("GET", "/view/2/edit") => MockResponse::status(403),
+                _ => MockResponse::status(404),
+            }
+        })
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn edit_preserves_unchanged_fields() {
+        let server = edit_server();
+        let session = Session::<NyaaCategory>::new(server.url());
+        let changes = TorrentChanges::new()
+            .name("[Group] Show - 01")
+            .hidden(false)
+            .complete(true);
+
+        session.edit_torrent(TorrentId(1), &changes).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn delete_own_torrent() {
+        let server = edit_server();
+        let session = Session::<NyaaCategory>::new(server.url());
+
+        session.delete_torrent(TorrentId(1)).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn edit_reports_permission_errors() {
+        let server = edit_server();
+        let session = Session::<NyaaCategory>::new(server.url());
+
+        let err = session.delete_torrent(TorrentId(2)).await.unwrap_err();
+        assert!(matches!(err, Error::PermissionError(_)));
+        let err = session.delete_torrent(TorrentId(3)).await.unwrap_err();
+        assert!(matches!(err, Error::NotFoundError(_)));
+    }
+}
@@ -1,16 +1,72 @@
+use crate::lookup::InfoHash;
+
 #[derive(Debug, thiserror::Error)]
 pub enum Error {
     #[error("Request error: {0}")]
     RequestError(#[from] reqwest::Error),
 
+    #[error("HTTP error: {0}")]
+    HttpError(#[from] http::Error),
+
     #[error("Selector error: {0}")]
     SelectorError(String),
 
+    #[error("Config error: {0}")]
+    ConfigError(String),
+
+    #[error("Category error: {0}")]
+    CategoryError(String),
+
+    #[error("Filter error: {0}")]
+    FilterError(String),
+
     #[error("Size parsing error: {0}")]
     SizeParsingError(String),
 
+    #[error("Login error: {0}")]
+    LoginError(String),
+
+    #[error("Form error: {0}")]
+    FormError(String),
+
+    #[error("Upload error: {0}")]
+    UploadError(String),
+
+    #[error("Permission error: {0}")]
+    PermissionError(String),
+
+    #[error("Not found: {0}")]
+    NotFoundError(String),
+
+    #[error("Not found: no torrent with info hash {0}")]
+    InfoHashNotFound(InfoHash),
+
+    #[error("Info hash error: {0}")]
+    InfoHashError(String),
+
+    #[error("Replay error: {0}")]
+    ReplayError(String),
+
+    #[error("Unexpected status: {0}")]
+    StatusError(u16),
+
+    #[error("JSON error: {0}")]
+    JsonError(#[from] serde_json::Error),
+
+    #[error("IO error: {0}")]
+    IoError(#[from] std::io::Error),
+
+    /// A background task panicked or was cancelled, with the `tokio`
+    /// feature
     #[error("Join error: {0}")]
-    JoinError(#[from] tokio::task::JoinError),
+    JoinError(Box<dyn std::error::Error + Send + Sync>),
+}
+
+#[cfg(feature = "tokio")]
+impl From<tokio::task::JoinError> for Error {
+    fn from(err: tokio::task::JoinError) -> Self {
+        Error::JoinError(Box::new(err))
+    }
 }
 
 pub type Result<T> = std::result::Result<T, Error>;
@@ -1,36 +1,206 @@
+use http::Method;
 use scraper::{ElementRef, Html, Selector};
-use std::sync::OnceLock;
+use serde::{Deserialize, Serialize};
+use std::sync::{Arc, OnceLock};
+use tracing::{event, Level};
 
+use crate::category::is_code;
 use crate::error::{Error, Result};
-use crate::model::{Size, Torrent};
-
-static ITEM_SELECTOR: OnceLock<Selector> = OnceLock::new();
-static TITLE_SELECTOR: OnceLock<Selector> = OnceLock::new();
-static TORRENT_LINK_SELECTOR: OnceLock<Selector> = OnceLock::new();
-static MAGNET_SELECTOR: OnceLock<Selector> = OnceLock::new();
-static SEEDERS_SELECTOR: OnceLock<Selector> = OnceLock::new();
-static LEECHERS_SELECTOR: OnceLock<Selector> = OnceLock::new();
-static DATE_SELECTOR: OnceLock<Selector> = OnceLock::new();
-static DOWNLOADS_SELECTOR: OnceLock<Selector> = OnceLock::new();
-static SIZE_SELECTOR: OnceLock<Selector> = OnceLock::new();
+use crate::model::{Size, Torrent, TorrentId};
+use crate::transport::{self, RequestBuilderExt, ResponseExt, Transport};
 
-pub fn extract(html: &str, base_url: &str) -> Result<Vec<Torrent>> {
-    let document = Html::parse_document(html);
-    let selector = ITEM_SELECTOR
-        .get_or_init(|| Selector::parse("table>tbody>tr").unwrap());
-    let items = document.select(selector);
-    let mut res_vec: Vec<Torrent> = Vec::with_capacity(75);
-
-    for item in items {
-        let title = extract_title(item)?;
-        let torrent_link = extract_torrent_link(item, base_url)?;
-        let magnet = extract_magnet_url(item)?;
-        let seeders = extract_seeders(item)?;
-        let leechers = extract_leechers(item)?;
-        let downloads = extract_downloads(item)?;
-        let size = extract_size(item)?;
-        let date = extract_date(item)?;
-        let torrent = Torrent {
+static DEFAULT_EXTRACTOR: OnceLock<Extractor> = OnceLock::new();
+static VIEW_CELL_SELECTOR: OnceLock<Selector> = OnceLock::new();
+static USER_LINK_SELECTOR: OnceLock<Selector> = OnceLock::new();
+
+/// Where [`Extractor`] finds each field of a search result page
+///
+/// The defaults match nyaa's current markup. Every field is optional when
+/// deserializing, so a hotfix only needs to list what changed.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ExtractorConfig {
+    /// one row per torrent
+    pub row: String,
+    pub columns: Columns,
+    /// link to the category's listing, within the category column
+    pub category: String,
+    /// title link, within the name column
+    pub title: String,
+    /// `.torrent` link, within the links column
+    pub torrent_link: String,
+    /// magnet link, within the links column
+    pub magnet: String,
+    /// attribute holding the url of both links
+    pub link_attribute: String,
+    /// attribute of the date column holding the unix timestamp
+    pub timestamp_attribute: String,
+}
+
+impl Default for ExtractorConfig {
+    fn default() -> Self {
+        Self {
+            row: "table>tbody>tr".into(),
+            columns: Columns::default(),
+            category: "a".into(),
+            title: "a:last-child".into(),
+            torrent_link: "a:first-child".into(),
+            magnet: "a:last-child".into(),
+            link_attribute: "href".into(),
+            timestamp_attribute: "data-timestamp".into(),
+        }
+    }
+}
+
+/// Positions of the listing columns, counting `<td>`s from 1
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Columns {
+    pub category: usize,
+    pub name: usize,
+    pub links: usize,
+    pub size: usize,
+    pub date: usize,
+    pub seeders: usize,
+    pub leechers: usize,
+    pub downloads: usize,
+}
+
+impl Default for Columns {
+    fn default() -> Self {
+        Self {
+            category: 1,
+            name: 2,
+            links: 3,
+            size: 4,
+            date: 5,
+            seeders: 6,
+            leechers: 7,
+            downloads: 8,
+        }
+    }
+}
+
+impl ExtractorConfig {
+    /// Read a config from TOML, e.g.
+    ///
+    /// ```toml
+    /// row = "table.torrent-list>tbody>tr"
+    ///
+    /// [columns]
+    /// size = 5
+    /// date = 4
+    /// ```
+    #[cfg(feature = "toml")]
+    pub fn from_toml(s: &str) -> Result<Self> {
+        toml::from_str(s).map_err(|err| Error::ConfigError(err.to_string()))
+    }
+
+    /// Compile the selectors
+    pub fn build(&self) -> Result<Extractor> {
+        let cell = |column: usize| format!("td:nth-of-type({})", column);
+        let columns = &self.columns;
+        Ok(Extractor {
+            row: selector(&self.row)?,
+            category: selector(&format!(
+                "{}>{}",
+                cell(columns.category),
+                self.category
+            ))?,
+            title: selector(&format!("{}>{}", cell(columns.name), self.title))?,
+            torrent_link: selector(&format!(
+                "{}>{}",
+                cell(columns.links),
+                self.torrent_link
+            ))?,
+            magnet: selector(&format!(
+                "{}>{}",
+                cell(columns.links),
+                self.magnet
+            ))?,
+            size: selector(&cell(columns.size))?,
+            date: selector(&cell(columns.date))?,
+            seeders: selector(&cell(columns.seeders))?,
+            leechers: selector(&cell(columns.leechers))?,
+            downloads: selector(&cell(columns.downloads))?,
+            config: self.clone(),
+        })
+    }
+}
+
+fn selector(s: &str) -> Result<Selector> {
+    Selector::parse(s).map_err(|err| {
+        Error::ConfigError(format!("Invalid selector {}: {}", s, err))
+    })
+}
+
+/// A parser of search result pages, built from an [`ExtractorConfig`]
+#[derive(Debug, Clone)]
+pub struct Extractor {
+    row: Selector,
+    category: Selector,
+    title: Selector,
+    torrent_link: Selector,
+    magnet: Selector,
+    size: Selector,
+    date: Selector,
+    seeders: Selector,
+    leechers: Selector,
+    downloads: Selector,
+    config: ExtractorConfig,
+}
+
+impl Default for Extractor {
+    fn default() -> Self {
+        ExtractorConfig::default()
+            .build()
+            .expect("default selectors are valid")
+    }
+}
+
+impl Extractor {
+    pub fn config(&self) -> &ExtractorConfig {
+        &self.config
+    }
+
+    pub(crate) fn row_selector(&self) -> &Selector {
+        &self.row
+    }
+
+    /// Parse the torrents of a search result page.
+    ///
+    /// `base_url` is prepended to the relative download links.
+    pub fn extract(&self, html: &str, base_url: &str) -> Result<Vec<Torrent>> {
+        let document = Html::parse_document(html);
+        self.extract_rows(&document, base_url).collect()
+    }
+
+    /// Parse every row of a page on its own.
+    pub(crate) fn extract_rows<'a>(
+        &'a self,
+        document: &'a Html,
+        base_url: &'a str,
+    ) -> impl Iterator<Item = Result<Torrent>> + 'a {
+        document
+            .select(&self.row)
+            .map(move |item| self.extract_row(item, base_url))
+    }
+
+    fn extract_row(
+        &self,
+        item: ElementRef<'_>,
+        base_url: &str,
+    ) -> Result<Torrent> {
+        let title = self.extract_title(item)?;
+        let torrent_link = self.extract_torrent_link(item, base_url)?;
+        let magnet = self.extract_magnet_url(item)?;
+        let seeders = extract_count(item, &self.seeders, "Seeders")?;
+        let leechers = extract_count(item, &self.leechers, "Leechers")?;
+        let downloads = extract_count(item, &self.downloads, "Downloads")?;
+        let size = self.extract_size(item)?;
+        let date = self.extract_date(item)?;
+        let category = self.extract_category(item);
+        Ok(Torrent {
             title,
             link: torrent_link,
             magnet_url: magnet,
@@ -39,116 +209,234 @@ pub fn extract(html: &str, base_url: &str) -> Result<Vec<Torrent>> {
             leechers,
             downloads,
             size,
-        };
-        res_vec.push(torrent);
+            category,
+            uploader: None,
+        })
     }
-    Ok(res_vec)
-}
 
-fn extract_title(item: ElementRef<'_>) -> Result<String> {
-    let selector = TITLE_SELECTOR.get_or_init(|| {
-        Selector::parse("td:nth-of-type(2)>a:last-child").unwrap()
-    });
-    let title = item
-        .select(selector)
-        .next()
-        .ok_or(Error::SelectorError("Title not found".into()))?;
-    Ok(title.text().collect())
+    /// The `c` parameter of the category link, which custom layouts may
+    /// lack, so it is optional
+    fn extract_category(&self, item: ElementRef<'_>) -> Option<String> {
+        let link = item.select(&self.category).next()?;
+        let href = link.value().attr(&self.config.link_attribute)?;
+        let (_, query) = href.split_once('?')?;
+        query
+            .split('&')
+            .find_map(|param| param.strip_prefix("c="))
+            .filter(|code| is_code(code))
+            .map(str::to_string)
+    }
+
+    fn extract_title(&self, item: ElementRef<'_>) -> Result<String> {
+        let title = item
+            .select(&self.title)
+            .next()
+            .ok_or(Error::SelectorError("Title not found".into()))?;
+        Ok(title.text().collect())
+    }
+
+    fn extract_torrent_link(
+        &self,
+        item: ElementRef<'_>,
+        base_url: &str,
+    ) -> Result<String> {
+        let link = item
+            .select(&self.torrent_link)
+            .next()
+            .ok_or(Error::SelectorError("Link not found".into()))?;
+        link.value()
+            .attr(&self.config.link_attribute)
+            .ok_or(Error::SelectorError("Link not found".into()))
+            .map(|s| format!("{}{}", base_url, s))
+    }
+
+    fn extract_magnet_url(&self, item: ElementRef<'_>) -> Result<String> {
+        let link = item
+            .select(&self.magnet)
+            .next()
+            .ok_or(Error::SelectorError("magnet not found".into()))?;
+        link.value()
+            .attr(&self.config.link_attribute)
+            .ok_or(Error::SelectorError("magnet not found".into()))
+            .map(|s| s.to_string())
+    }
+
+    fn extract_size(&self, item: ElementRef<'_>) -> Result<Size> {
+        let size = item
+            .select(&self.size)
+            .next()
+            .ok_or(Error::SelectorError("Size not found".into()))?;
+        size.text().collect::<String>().parse()
+    }
+
+    fn extract_date(
+        &self,
+        item: ElementRef<'_>,
+    ) -> Result<chrono::DateTime<chrono::Utc>> {
+        let date = item
+            .select(&self.date)
+            .next()
+            .ok_or(Error::SelectorError("Date not found".into()))?;
+        let time_stamp = date
+            .attr(&self.config.timestamp_attribute)
+            .and_then(|s| s.parse::<i64>().ok())
+            .ok_or(Error::SelectorError("Date not found".into()))?;
+        chrono::DateTime::from_timestamp(time_stamp, 0).ok_or_else(|| {
+            Error::SelectorError(format!("Invalid timestamp: {}", time_stamp))
+        })
+    }
 }
 
-fn extract_torrent_link(
-    item: ElementRef<'_>,
-    base_url: &str,
-) -> Result<String> {
-    let selector = TORRENT_LINK_SELECTOR.get_or_init(|| {
-        Selector::parse("td:nth-of-type(3)>a:first-child").unwrap()
-    });
-    let link = item
-        .select(selector)
-        .next()
-        .ok_or(Error::SelectorError("Link not found".into()))?;
-    link.value()
-        .attr("href")
-        .ok_or(Error::SelectorError("Link not found".into()))
-        .map(|s| format!("{}{}", base_url, s))
+/// Parse the torrents of a search result page with the default
+/// [`Extractor`].
+///
+/// This is a pure function, so it can run on any executor or none at all.
+/// `base_url` is prepended to the relative download links.
+pub fn extract(html: &str, base_url: &str) -> Result<Vec<Torrent>> {
+    DEFAULT_EXTRACTOR
+        .get_or_init(Extractor::default)
+        .extract(html, base_url)
 }
 
-fn extract_magnet_url(item: ElementRef<'_>) -> Result<String> {
-    let selector = MAGNET_SELECTOR.get_or_init(|| {
-        Selector::parse("td:nth-of-type(3)>a:last-child").unwrap()
-    });
-    let link = item
-        .select(selector)
-        .next()
-        .ok_or(Error::SelectorError("magnet not found".into()))?;
-    link.value()
-        .attr("href")
-        .ok_or(Error::SelectorError("magnet not found".into()))
-        .map(|s| s.to_string())
+/// Read the uploader from a torrent's view page, `None` for anonymous
+/// uploads.
+pub fn extract_uploader(html: &str) -> Option<String> {
+    let document = Html::parse_document(html);
+    let cell_selector = VIEW_CELL_SELECTOR
+        .get_or_init(|| Selector::parse(".panel-body .row>div").unwrap());
+    let link_selector = USER_LINK_SELECTOR
+        .get_or_init(|| Selector::parse("a[href^=\"/user/\"]").unwrap());
+    let mut cells = document.select(cell_selector);
+    cells
+        .find(|cell| cell.text().collect::<String>().trim() == "Submitter:")?;
+    let link = cells.next()?.select(link_selector).next()?;
+    Some(link.text().collect::<String>().trim().to_string())
 }
 
-fn extract_seeders(item: ElementRef<'_>) -> Result<u32> {
-    let selector = SEEDERS_SELECTOR
-        .get_or_init(|| Selector::parse("td:nth-of-type(6)").unwrap());
-    let seeders = item
-        .select(selector)
-        .next()
-        .ok_or(Error::SelectorError("Seeders not found".into()))?;
-    let seeders_str: String = seeders.text().collect();
-    seeders_str
-        .parse::<u32>()
-        .map_err(|_| Error::SelectorError("Seeders not found".into()))
+/// Fetch the view page of torrent `id` and read its uploader.
+pub(crate) async fn fetch_uploader<T: Transport>(
+    transport: &T,
+    base_url: &str,
+    id: TorrentId,
+) -> Result<Option<String>> {
+    let url = format!("{}/view/{}", base_url, id);
+
+    event!(Level::DEBUG, "url = {}", url);
+
+    let res = transport
+        .send(transport::request(Method::GET, &url).empty()?)
+        .await?;
+    if !res.status().is_success() {
+        return Err(Error::StatusError(res.status().as_u16()));
+    }
+    Ok(extract_uploader(&res.text()))
 }
 
-fn extract_leechers(item: ElementRef<'_>) -> Result<u32> {
-    let selector = LEECHERS_SELECTOR
-        .get_or_init(|| Selector::parse("td:nth-of-type(7)").unwrap());
-    let leechers = item
-        .select(selector)
-        .next()
-        .ok_or(Error::SelectorError("Leechers not found".into()))?;
-    let leechers_str: String = leechers.text().collect();
-    leechers_str
-        .parse::<u32>()
-        .map_err(|_| Error::SelectorError("Leechers not found".into()))
+/// Parse a fetched page, on tokio's blocking pool when the `tokio` feature is
+/// enabled and inline otherwise.
+pub(crate) async fn extract_owned(
+    extractor: Arc<Extractor>,
+    html: String,
+    base_url: String,
+) -> Result<Vec<Torrent>> {
+    #[cfg(feature = "tokio")]
+    {
+        tokio::task::spawn_blocking(move || extractor.extract(&html, &base_url))
+            .await?
+    }
+    #[cfg(not(feature = "tokio"))]
+    {
+        extractor.extract(&html, &base_url)
+    }
 }
 
-fn extract_downloads(item: ElementRef<'_>) -> Result<u32> {
-    let selector = DOWNLOADS_SELECTOR
-        .get_or_init(|| Selector::parse("td:nth-of-type(8)").unwrap());
-    let downloads = item
+fn extract_count(
+    item: ElementRef<'_>,
+    selector: &Selector,
+    name: &str,
+) -> Result<u32> {
+    let count = item
         .select(selector)
         .next()
-        .ok_or(Error::SelectorError("Downloads not found".into()))?;
-    let downloads_str: String = downloads.text().collect();
-    downloads_str
+        .ok_or(Error::SelectorError(format!("{} not found", name)))?;
+    let count_str: String = count.text().collect();
+    count_str
         .parse::<u32>()
-        .map_err(|_| Error::SelectorError("Downloads not found".into()))
+        .map_err(|_| Error::SelectorError(format!("{} not found", name)))
 }
 
-fn extract_size(item: ElementRef<'_>) -> Result<Size> {
-    let selector = SIZE_SELECTOR
-        .get_or_init(|| Selector::parse("td:nth-of-type(4)").unwrap());
-    let size = item
-        .select(selector)
-        .next()
-        .ok_or(Error::SelectorError("Size not found".into()))?;
-    size.text().collect::<String>().parse()
-}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::LISTING;
 
-fn extract_date(item: ElementRef<'_>) -> Result<chrono::DateTime<chrono::Utc>> {
-    let selector = DATE_SELECTOR
-        .get_or_init(|| Selector::parse("td:nth-of-type(5)").unwrap());
-    let date = item
-        .select(selector)
-        .next()
-        .ok_or(Error::SelectorError("Date not found".into()))?;
-    date.attr("data-timestamp")
-        .ok_or(Error::SelectorError("Date not found".into()))
-        .map(|s| {
-            let time_stamp = s.parse::<i64>().unwrap();
-            let ts_in_millis = time_stamp * 1000;
-            chrono::DateTime::from_timestamp_millis(ts_in_millis)
-                .unwrap_or_default()
-        })
+    #[test]
+    fn extract_listing() {
+        let torrents = extract(LISTING, "https://nyaa.si").unwrap();
+        assert_eq!(torrents.len(), 1);
+
+        let torrent = &torrents[0];
+        assert_eq!(torrent.title, "[Group] Show - 01");
+        assert_eq!(torrent.link, "https://nyaa.si/download/1.torrent");
+        assert!(torrent.magnet_url.starts_with("magnet:?xt=urn:btih:"));
+        assert_eq!(torrent.size, Size::GB(1.2));
+        assert_eq!(torrent.category.as_deref(), Some("1_2"));
+        assert_eq!(torrent.date.timestamp(), 1700000000);
+        assert_eq!(
+            (torrent.seeders, torrent.leechers, torrent.downloads),
+            (10, 2, 300)
+        );
+    }
+
+    #[test]
+    fn reject_bad_timestamps() {
+        let garbled = LISTING.replace("1700000000", "17e8");
+        assert!(matches!(
+            extract(&garbled, "https://nyaa.si"),
+            Err(Error::SelectorError(_))
+        ));
+
+        let far_future = LISTING.replace("1700000000", &i64::MAX.to_string());
+        let err = extract(&far_future, "https://nyaa.si").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            format!("Selector error: Invalid timestamp: {}", i64::MAX)
+        );
+    }
+
+    #[test]
+    fn extract_with_custom_config() {
+        // nyaa swapping the size and date columns
+        let size = r#"<td class="text-center">1.2 GiB</td>"#;
+        let date = r#"<td class="text-center" data-timestamp="1700000000">2023-11-14 22:13</td>"#;
+        let drifted = LISTING
+            .replace(size, "SIZE")
+            .replace(date, size)
+            .replace("SIZE", date);
+        assert!(extract(&drifted, "https://nyaa.si").is_err());
+
+        let mut config = ExtractorConfig::default();
+        config.columns.size = 5;
+        config.columns.date = 4;
+        let extractor = config.build().unwrap();
+        let torrents = extractor.extract(&drifted, "https://nyaa.si").unwrap();
+        assert_eq!(torrents[0].size, Size::GB(1.2));
+        assert_eq!(torrents[0].date.timestamp(), 1700000000);
+
+        config.row = "tr[".into();
+        assert!(matches!(config.build(), Err(Error::ConfigError(_))));
+    }
+
+    #[cfg(feature = "toml")]
+    #[test]
+    fn config_from_toml() {
+        let config = ExtractorConfig::from_toml(
+            "row = \"table.torrent-list>tbody>tr\"\n[columns]\nsize = 5\n",
+        )
+        .unwrap();
+        assert_eq!(config.row, "table.torrent-list>tbody>tr");
+        assert_eq!(config.columns.size, 5);
+        assert_eq!(config.columns.date, 5);
+        assert_eq!(config.title, ExtractorConfig::default().title);
+    }
 }
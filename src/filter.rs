@@ -0,0 +1,579 @@
+//! Client-side filters, for conditions nyaa's `f` parameter cannot express.
+//!
+//! A filter parses from whitespace-separated terms, all of which must match:
+//!
+//! | term | matches |
+//! | --- | --- |
+//! | `seeders:10..` / `seeders:..100` / `seeders:10..100` | seeder range, inclusive |
+//! | `size:500MiB..2GiB` | size range, inclusive |
+//! | `date:2024-01-01..2024-06-30` / `date:2024-03-01` | upload date range, inclusive of whole days, or RFC 3339 times |
+//! | `title:"regex"` / `-title:"regex"` | titles matching / not matching, ignoring case |
+//! | `uploader:a,b` / `-uploader:a,b` | uploader allow / deny list, ignoring case |
+//! | `category:1_2,4_0` | category set, main categories including their sub-categories |
+//!
+//! Listings don't show uploaders, so fill [`Torrent::uploader`] with
+//! `NyaaClient::uploader` before filtering on them: torrents without one
+//! never pass an allow list.
+//!
+//! Quote values containing spaces, with `\"` for a quote inside them.
+//!
+//! ```
+//! use nyaa_si::filter::TorrentFilter;
+//!
+//! let filter: TorrentFilter =
+//!     r#"seeders:5.. size:..4GiB -title:"\b(hevc|x265)\b""#.parse()?;
+//! # Ok::<(), nyaa_si::error::Error>(())
+//! ```
+
+use std::pin::Pin;
+use std::str::FromStr;
+use std::task::{Context, Poll};
+
+use chrono::{DateTime, NaiveDate, NaiveTime, Utc};
+use futures_core::Stream;
+use regex::{Regex, RegexBuilder};
+
+use crate::{
+    category::{is_code, split_code, Category},
+    error::{Error, Result},
+    model::{Size, Torrent},
+};
+
+/// Something a [`TorrentFilter`] can check
+pub trait Filterable {
+    fn torrent(&self) -> &Torrent;
+
+    fn uploader(&self) -> Option<&str> {
+        self.torrent().uploader.as_deref()
+    }
+
+    /// Category code, e.g. `1_2`
+    fn category(&self) -> Option<&str> {
+        self.torrent().category.as_deref()
+    }
+}
+
+impl Filterable for Torrent {
+    fn torrent(&self) -> &Torrent {
+        self
+    }
+}
+
+impl<T: Filterable + ?Sized> Filterable for &T {
+    fn torrent(&self) -> &Torrent {
+        (**self).torrent()
+    }
+
+    fn uploader(&self) -> Option<&str> {
+        (**self).uploader()
+    }
+
+    fn category(&self) -> Option<&str> {
+        (**self).category()
+    }
+}
+
+/// Conditions on torrents, all of which must hold
+#[derive(Debug, Clone, Default)]
+pub struct TorrentFilter {
+    min_seeders: Option<u32>,
+    max_seeders: Option<u32>,
+    min_size: Option<Size>,
+    max_size: Option<Size>,
+    since: Option<DateTime<Utc>>,
+    until: Option<DateTime<Utc>>,
+    titles: Vec<Regex>,
+    excluded_titles: Vec<Regex>,
+    uploaders: Option<Vec<String>>,
+    denied_uploaders: Vec<String>,
+    categories: Option<Vec<String>>,
+}
+
+impl TorrentFilter {
+    /// A filter passing everything
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn min_seeders(mut self, seeders: u32) -> Self {
+        self.min_seeders = Some(seeders);
+        self
+    }
+
+    pub fn max_seeders(mut self, seeders: u32) -> Self {
+        self.max_seeders = Some(seeders);
+        self
+    }
+
+    pub fn min_size(mut self, size: Size) -> Self {
+        self.min_size = Some(size);
+        self
+    }
+
+    pub fn max_size(mut self, size: Size) -> Self {
+        self.max_size = Some(size);
+        self
+    }
+
+    /// Uploaded at or after `date`
+    pub fn since(mut self, date: DateTime<Utc>) -> Self {
+        self.since = Some(date);
+        self
+    }
+
+    /// Uploaded at or before `date`
+    pub fn until(mut self, date: DateTime<Utc>) -> Self {
+        self.until = Some(date);
+        self
+    }
+
+    /// Require titles to match `regex`
+    pub fn title(mut self, regex: Regex) -> Self {
+        self.titles.push(regex);
+        self
+    }
+
+    /// Reject titles matching `regex`
+    pub fn exclude_title(mut self, regex: Regex) -> Self {
+        self.excluded_titles.push(regex);
+        self
+    }
+
+    /// Only allow `uploader`, along with any others allowed so far
+    pub fn allow_uploader<S: Into<String>>(mut self, uploader: S) -> Self {
+        self.uploaders
+            .get_or_insert_with(Vec::new)
+            .push(uploader.into());
+        self
+    }
+
+    pub fn deny_uploader<S: Into<String>>(mut self, uploader: S) -> Self {
+        self.denied_uploaders.push(uploader.into());
+        self
+    }
+
+    /// Only allow `category` and its sub-categories, along with any others
+    /// allowed so far
+    pub fn category<C: Category>(self, category: C) -> Self {
+        self.category_code(category.code())
+    }
+
+    fn category_code(mut self, code: &str) -> Self {
+        self.categories
+            .get_or_insert_with(Vec::new)
+            .push(code.to_string());
+        self
+    }
+
+    /// Whether `item` meets every condition
+    pub fn matches<T: Filterable + ?Sized>(&self, item: &T) -> bool {
+        let torrent = item.torrent();
+        let title = &torrent.title;
+        let uploader = item.uploader();
+
+        self.min_seeders.map_or(true, |min| torrent.seeders >= min)
+            && self.max_seeders.map_or(true, |max| torrent.seeders <= max)
+            && self.min_size.map_or(true, |min| torrent.size >= min)
+            && self.max_size.map_or(true, |max| torrent.size <= max)
+            && self.since.map_or(true, |since| torrent.date >= since)
+            && self.until.map_or(true, |until| torrent.date <= until)
+            && self.titles.iter().all(|regex| regex.is_match(title))
+            && !self
+                .excluded_titles
+                .iter()
+                .any(|regex| regex.is_match(title))
+            && self.uploaders.as_ref().map_or(true, |allowed| {
+                uploader.is_some_and(|uploader| contains(allowed, uploader))
+            })
+            && !uploader.is_some_and(|uploader| {
+                contains(&self.denied_uploaders, uploader)
+            })
+            && self.categories.as_ref().map_or(true, |categories| {
+                item.category().is_some_and(|code| {
+                    categories.iter().any(|allowed| in_category(allowed, code))
+                })
+            })
+    }
+
+    /// Keep the items that match, e.g. the results of several pages
+    pub fn apply<'a, I>(
+        &'a self,
+        items: I,
+    ) -> impl Iterator<Item = I::Item> + 'a
+    where
+        I: IntoIterator,
+        I::IntoIter: 'a,
+        I::Item: Filterable,
+    {
+        items.into_iter().filter(move |item| self.matches(item))
+    }
+
+    /// Keep the items of `stream` that match
+    ///
+    /// Streams that are not `Unpin` can be pinned with `Box::pin` first.
+    pub fn apply_stream<S>(&self, stream: S) -> FilterStream<S>
+    where
+        S: Stream + Unpin,
+        S::Item: Filterable,
+    {
+        FilterStream {
+            filter: self.clone(),
+            stream,
+        }
+    }
+}
+
+/// The matching torrents of a stream, see [`TorrentFilter::apply_stream`]
+#[derive(Debug)]
+pub struct FilterStream<S> {
+    filter: TorrentFilter,
+    stream: S,
+}
+
+impl<S> Stream for FilterStream<S>
+where
+    S: Stream + Unpin,
+    S::Item: Filterable,
+{
+    type Item = S::Item;
+
+    fn poll_next(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<S::Item>> {
+        loop {
+            match Pin::new(&mut self.stream).poll_next(cx) {
+                Poll::Ready(Some(item)) if !self.filter.matches(&item) => {}
+                poll => return poll,
+            }
+        }
+    }
+}
+
+fn contains(names: &[String], name: &str) -> bool {
+    names
+        .iter()
+        .any(|allowed| allowed.eq_ignore_ascii_case(name))
+}
+
+/// Whether `code` is `allowed` or, for a main category, one of its children
+fn in_category(allowed: &str, code: &str) -> bool {
+    match split_code(allowed) {
+        ("0", _) => true,
+        (main, "0") => split_code(code).0 == main,
+        _ => allowed == code,
+    }
+}
+
+impl FromStr for TorrentFilter {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        terms(s)?
+            .into_iter()
+            .try_fold(TorrentFilter::new(), |filter, term| {
+                filter.with_term(&term)
+            })
+    }
+}
+
+impl TorrentFilter {
+    fn with_term(self, term: &str) -> Result<Self> {
+        let invalid = |reason: &str| {
+            Error::FilterError(format!("Invalid term {}: {}", term, reason))
+        };
+        let (key, value) = term
+            .split_once(':')
+            .ok_or_else(|| invalid("expected key:value"))?;
+        if value.is_empty() {
+            return Err(invalid("empty value"));
+        }
+
+        Ok(match key {
+            "seeders" => {
+                let (min, max) = range(value, |s| s.parse().ok())
+                    .ok_or_else(|| invalid("expected a range like 10..100"))?;
+                Self {
+                    min_seeders: min.or(self.min_seeders),
+                    max_seeders: max.or(self.max_seeders),
+                    ..self
+                }
+            }
+            "size" => {
+                let (min, max) = range(value, parse_size).ok_or_else(|| {
+                    invalid("expected a range like 1GiB..4GiB")
+                })?;
+                Self {
+                    min_size: min.or(self.min_size),
+                    max_size: max.or(self.max_size),
+                    ..self
+                }
+            }
+            "date" => {
+                let (since, until) = match value.split_once("..") {
+                    Some(_) => range(value, |s| parse_date(s, false))
+                        .zip(range(value, |s| parse_date(s, true)))
+                        .map(|((since, _), (_, until))| (since, until)),
+                    None => parse_date(value, false)
+                        .zip(parse_date(value, true))
+                        .map(|(since, until)| (Some(since), Some(until))),
+                }
+                .ok_or_else(|| invalid("expected dates like 2024-01-31"))?;
+                Self {
+                    since: since.or(self.since),
+                    until: until.or(self.until),
+                    ..self
+                }
+            }
+            "title" => self.title(regex(value).map_err(|err| invalid(&err))?),
+            "-title" => {
+                self.exclude_title(regex(value).map_err(|err| invalid(&err))?)
+            }
+            "uploader" => list(value).fold(self, Self::allow_uploader),
+            "-uploader" => list(value).fold(self, Self::deny_uploader),
+            "category" => {
+                let mut filter = self;
+                for code in list(value) {
+                    if !is_code(code) {
+                        return Err(invalid("expected codes like 1_2"));
+                    }
+                    filter = filter.category_code(code);
+                }
+                filter
+            }
+            _ => return Err(invalid("unknown key")),
+        })
+    }
+}
+
+/// Split `s` on whitespace outside of double quotes, unquoting values
+fn terms(s: &str) -> Result<Vec<String>> {
+    let mut terms = Vec::new();
+    let mut term = String::new();
+    let mut quoted = false;
+    let mut chars = s.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' => quoted = !quoted,
+            '\\' if quoted && chars.peek() == Some(&'"') => {
+                term.push('"');
+                chars.next();
+            }
+            c if c.is_whitespace() && !quoted => {
+                if !term.is_empty() {
+                    terms.push(std::mem::take(&mut term));
+                }
+            }
+            c => term.push(c),
+        }
+    }
+    if quoted {
+        return Err(Error::FilterError(format!("Unclosed quote in {}", s)));
+    }
+    if !term.is_empty() {
+        terms.push(term);
+    }
+    Ok(terms)
+}
+
+/// `min..max`, either of them optional
+fn range<T>(
+    s: &str,
+    parse: impl Fn(&str) -> Option<T>,
+) -> Option<(Option<T>, Option<T>)> {
+    let (min, max) = s.split_once("..")?;
+    let bound = |s: &str| match s.trim() {
+        "" => Some(None),
+        s => parse(s).map(Some),
+    };
+    Some((bound(min)?, bound(max)?))
+}
+
+/// `1.5GiB` or `1.5 GiB`
+fn parse_size(s: &str) -> Option<Size> {
+    let unit = s.find(|c: char| c.is_ascii_alphabetic())?;
+    let (value, unit) = s.split_at(unit);
+    format!("{} {}", value.trim(), unit).parse().ok()
+}
+
+/// An RFC 3339 time, or the start or end of a day
+fn parse_date(s: &str, end_of_day: bool) -> Option<DateTime<Utc>> {
+    if let Ok(date) = DateTime::parse_from_rfc3339(s) {
+        return Some(date.with_timezone(&Utc));
+    }
+    let date = NaiveDate::parse_from_str(s, "%Y-%m-%d").ok()?;
+    let time = if end_of_day {
+        NaiveTime::from_hms_opt(23, 59, 59)?
+    } else {
+        NaiveTime::MIN
+    };
+    Some(date.and_time(time).and_utc())
+}
+
+fn regex(pattern: &str) -> std::result::Result<Regex, String> {
+    RegexBuilder::new(pattern)
+        .case_insensitive(true)
+        .build()
+        .map_err(|err| err.to_string())
+}
+
+fn list(s: &str) -> impl Iterator<Item = &str> {
+    s.split(',').map(str::trim).filter(|item| !item.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock;
+    use crate::testing::FakeTorrent;
+    use crate::NyaaCategory;
+
+    fn torrent(title: &str, seeders: u32, size: Size, day: u32) -> Torrent {
+        let date = NaiveDate::from_ymd_opt(2024, 1, day)
+            .unwrap()
+            .and_hms_opt(12, 0, 0)
+            .unwrap()
+            .and_utc();
+        mock::torrent(day)
+            .title(title)
+            .seeders(seeders)
+            .size(size)
+            .date(date)
+            .build()
+    }
+
+    #[test]
+    fn parse_and_apply() {
+        let torrents = vec![
+            torrent("[Group] Show - 01 [1080p]", 50, Size::GB(1.4), 1),
+            torrent("[Group] Show - 01 [1080p HEVC]", 80, Size::MB(700.0), 2),
+            torrent("[Group] Show - 01 [480p]", 2, Size::MB(200.0), 3),
+            torrent("[Group] Show - Batch", 40, Size::GB(20.0), 20),
+        ];
+        let filter: TorrentFilter = r#"
+            seeders:5.. size:500MiB..4GiB date:2024-01-01..2024-01-10
+            title:1080p -title:"\b(hevc|x265)\b"
+        "#
+        .parse()
+        .unwrap();
+        let titles: Vec<_> = filter
+            .apply(torrents.clone())
+            .map(|torrent| torrent.title)
+            .collect();
+        assert_eq!(titles, ["[Group] Show - 01 [1080p]"]);
+
+        let filter: TorrentFilter = "date:2024-01-20".parse().unwrap();
+        assert_eq!(filter.apply(&torrents).count(), 1);
+        assert_eq!(TorrentFilter::new().apply(&torrents).count(), 4);
+    }
+
+    #[test]
+    fn categories() {
+        let torrents = [
+            mock::torrent(1).category(NyaaCategory::AnimeEnglishTranslated),
+            mock::torrent(2).category(NyaaCategory::AnimeRaw),
+            mock::torrent(3).category(NyaaCategory::LiveActionRaw),
+        ]
+        .map(|torrent| torrent.build());
+        let ids = |filter: &str| {
+            let filter: TorrentFilter = filter.parse().unwrap();
+            filter
+                .apply(&torrents)
+                .map(|torrent| torrent.id().unwrap().0)
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(ids("category:1_0"), [1, 2]);
+        assert_eq!(ids("category:1_4,4_4"), [2, 3]);
+        assert_eq!(ids("category:0_0"), [1, 2, 3]);
+
+        let filter = TorrentFilter::new().category(NyaaCategory::LiveAction);
+        assert!(filter.matches(&torrents[2]));
+        let unknown = Torrent {
+            category: None,
+            ..torrents[2].clone()
+        };
+        assert!(!filter.matches(&unknown));
+    }
+
+    #[test]
+    fn uploaders() {
+        let torrents = [
+            mock::torrent(1).uploader("Alice").build(),
+            mock::torrent(2).uploader("bob").build(),
+            mock::torrent(3).uploader("carol").build(),
+            mock::torrent(4).build(),
+        ];
+        let ids = |filter: &str| {
+            let filter: TorrentFilter = filter.parse().unwrap();
+            filter
+                .apply(&torrents)
+                .map(|torrent| torrent.id().unwrap().0)
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(ids("uploader:alice,carol"), [1, 3]);
+        assert_eq!(ids("-uploader:alice"), [2, 3, 4]);
+
+        let fake = FakeTorrent::new(mock::torrent(5).build()).submitter("bob");
+        let filter = TorrentFilter::new().allow_uploader("BOB");
+        assert!(filter.matches(&fake));
+        assert!(!filter.matches(&fake.torrent));
+    }
+
+    #[tokio::test]
+    async fn filter_stream() {
+        struct Iter<I>(I);
+
+        impl<I: Iterator + Unpin> Stream for Iter<I> {
+            type Item = I::Item;
+
+            fn poll_next(
+                mut self: Pin<&mut Self>,
+                _: &mut Context<'_>,
+            ) -> Poll<Option<I::Item>> {
+                Poll::Ready(self.0.next())
+            }
+        }
+
+        let torrents = (1..=6).map(|id| mock::torrent(id).seeders(id).build());
+        let filter: TorrentFilter = "seeders:2..4".parse().unwrap();
+        let mut stream = filter.apply_stream(Iter(torrents));
+        let mut seeders = Vec::new();
+        while let Some(torrent) =
+            std::future::poll_fn(|cx| Pin::new(&mut stream).poll_next(cx)).await
+        {
+            seeders.push(torrent.seeders);
+        }
+        assert_eq!(seeders, [2, 3, 4]);
+    }
+
+    #[test]
+    fn reject_bad_terms() {
+        for (filter, error) in [
+            ("seeders", "Invalid term seeders: expected key:value"),
+            (
+                "seeders:10",
+                "Invalid term seeders:10: expected a range like 10..100",
+            ),
+            (
+                "size:1GB..",
+                "Invalid term size:1GB..: expected a range like 1GiB..4GiB",
+            ),
+            (
+                "date:yesterday",
+                "Invalid term date:yesterday: expected dates like 2024-01-31",
+            ),
+            (
+                "category:anime",
+                "Invalid term category:anime: expected codes like 1_2",
+            ),
+            ("leechers:1..", "Invalid term leechers:1..: unknown key"),
+            (r#"title:"a b"#, r#"Unclosed quote in title:"a b"#),
+        ] {
+            let err = filter.parse::<TorrentFilter>().unwrap_err();
+            assert_eq!(err.to_string(), format!("Filter error: {}", error));
+        }
+        assert!("title:(".parse::<TorrentFilter>().is_err());
+    }
+}
@@ -0,0 +1,223 @@
+use scraper::{ElementRef, Html, Selector};
+use std::sync::OnceLock;
+
+use crate::error::{Error, Result};
+
+static FORM_SELECTOR: OnceLock<Selector> = OnceLock::new();
+static FIELD_SELECTOR: OnceLock<Selector> = OnceLock::new();
+static OPTION_SELECTOR: OnceLock<Selector> = OnceLock::new();
+static ALERT_SELECTOR: OnceLock<Selector> = OnceLock::new();
+static CSRF_SELECTOR: OnceLock<Selector> = OnceLock::new();
+
+/// The fields of an html form, in document order, as they would be submitted
+/// by a browser.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub(crate) struct HtmlForm {
+    fields: Vec<(String, String)>,
+}
+
+impl HtmlForm {
+    /// Parse the first form in `html` that has a field named `field`.
+    ///
+    /// Nyaa pages start with the navbar's search form, so the form to submit
+    /// is told apart by one of its own fields rather than taken first.
+    pub fn find(html: &str, field: &str) -> Result<Self> {
+        let document = Html::parse_document(html);
+        let form_selector =
+            FORM_SELECTOR.get_or_init(|| Selector::parse("form").unwrap());
+        let field_selector = Selector::parse(&format!("[name=\"{field}\"]"))
+            .map_err(|_| Error::FormError(format!("Bad field: {field}")))?;
+        let form = document
+            .select(form_selector)
+            .find(|form| form.select(&field_selector).next().is_some())
+            .ok_or_else(|| {
+                Error::FormError(format!("Form with {field} not found"))
+            })?;
+        Ok(Self::from_element(form))
+    }
+
+    fn from_element(form: ElementRef<'_>) -> Self {
+        let selector = FIELD_SELECTOR.get_or_init(|| {
+            Selector::parse("input[name], textarea[name], select[name]")
+                .unwrap()
+        });
+        let mut fields = Vec::new();
+
+        for field in form.select(selector) {
+            let element = field.value();
+            let name = element.attr("name").unwrap_or_default().to_string();
+            if element.attr("disabled").is_some() {
+                continue;
+            }
+            let value = match element.name() {
+                "textarea" => field.text().collect(),
+                "select" => match select_value(field) {
+                    Some(value) => value,
+                    None => continue,
+                },
+                _ => match element.attr("type").unwrap_or("text") {
+                    "submit" | "button" | "reset" | "image" | "file" => {
+                        continue
+                    }
+                    "checkbox" | "radio"
+                        if element.attr("checked").is_none() =>
+                    {
+                        continue
+                    }
+                    "checkbox" | "radio" => {
+                        element.attr("value").unwrap_or("on").to_string()
+                    }
+                    _ => element.attr("value").unwrap_or_default().to_string(),
+                },
+            };
+            fields.push((name, value));
+        }
+        Self { fields }
+    }
+
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.fields
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// Replace the value of `name`, adding the field if it is missing.
+    pub fn set<S: Into<String>>(&mut self, name: &str, value: S) {
+        let value = value.into();
+        match self.fields.iter_mut().find(|(key, _)| key == name) {
+            Some((_, old)) => *old = value,
+            None => self.fields.push((name.to_string(), value)),
+        }
+    }
+
+    /// Check or uncheck a checkbox.
+    pub fn check(&mut self, name: &str, checked: bool) {
+        if checked {
+            self.set(name, "y");
+        } else {
+            self.fields.retain(|(key, _)| key != name);
+        }
+    }
+
+    pub fn csrf_token(&self) -> Result<&str> {
+        self.get("csrf_token")
+            .ok_or_else(|| Error::FormError("CSRF token not found".into()))
+    }
+
+    pub fn fields(&self) -> &[(String, String)] {
+        &self.fields
+    }
+}
+
+fn select_value(select: ElementRef<'_>) -> Option<String> {
+    let selector =
+        OPTION_SELECTOR.get_or_init(|| Selector::parse("option").unwrap());
+    let mut options = select.select(selector);
+    let first = options.next()?;
+    let chosen = std::iter::once(first)
+        .chain(options)
+        .find(|option| option.value().attr("selected").is_some())
+        .unwrap_or(first);
+    Some(
+        chosen
+            .value()
+            .attr("value")
+            .map(|value| value.to_string())
+            .unwrap_or_else(|| chosen.text().collect()),
+    )
+}
+
+/// Find the CSRF token of any form on a page.
+pub(crate) fn csrf_token(html: &str) -> Result<String> {
+    let document = Html::parse_document(html);
+    let selector = CSRF_SELECTOR
+        .get_or_init(|| Selector::parse("input[name=csrf_token]").unwrap());
+    document
+        .select(selector)
+        .find_map(|input| input.value().attr("value"))
+        .map(|token| token.to_string())
+        .ok_or_else(|| Error::FormError("CSRF token not found".into()))
+}
+
+/// Extract the first flashed or field error message from a page.
+pub(crate) fn error_message(html: &str) -> Option<String> {
+    let document = Html::parse_document(html);
+    let selector = ALERT_SELECTOR.get_or_init(|| {
+        Selector::parse(".alert-danger, .has-error .help-block").unwrap()
+    });
+    let alert = document.select(selector).next()?;
+    let text = alert.text().collect::<String>();
+    let message = text
+        .split_whitespace()
+        .filter(|word| *word != "×")
+        .collect::<Vec<_>>()
+        .join(" ");
+    Some(message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FORM: &str = r#"
+        <form class="navbar-form" action="/" method="get">
+            <input name="q" type="text" value="">
+        </form>
+        <form method="POST" id="edit">
+            <input id="csrf_token" name="csrf_token" type="hidden" value="tok">
+            <input name="display_name" type="text" value="[Group] Title">
+            <select name="category">
+                <option value="1_1">Anime - AMV</option>
+                <option value="1_2" selected>Anime - English</option>
+            </select>
+            <textarea name="description">line one
+line two</textarea>
+            <input name="is_hidden" type="checkbox" value="y" checked>
+            <input name="is_remake" type="checkbox" value="y">
+            <input name="submit" type="submit" value="Save">
+        </form>
+    "#;
+
+    #[test]
+    fn parse_form_fields() {
+        let form = HtmlForm::find(FORM, "display_name").unwrap();
+        assert_eq!(form.csrf_token().unwrap(), "tok");
+        assert_eq!(form.get("display_name"), Some("[Group] Title"));
+        assert_eq!(form.get("category"), Some("1_2"));
+        assert_eq!(form.get("description"), Some("line one\nline two"));
+        assert_eq!(form.get("is_hidden"), Some("y"));
+        assert_eq!(form.get("is_remake"), None);
+        assert_eq!(form.get("submit"), None);
+    }
+
+    #[test]
+    fn missing_form() {
+        let err = HtmlForm::find(FORM, "password").unwrap_err();
+        assert_eq!(err.to_string(), "Form error: Form with password not found");
+    }
+
+    #[test]
+    fn update_form_fields() {
+        let mut form = HtmlForm::find(FORM, "display_name").unwrap();
+        form.set("display_name", "[Group] Fixed");
+        form.check("is_hidden", false);
+        form.check("is_remake", true);
+        assert_eq!(form.get("display_name"), Some("[Group] Fixed"));
+        assert_eq!(form.get("is_hidden"), None);
+        assert_eq!(form.get("is_remake"), Some("y"));
+        assert_eq!(form.fields().len(), 5);
+    }
+
+    #[test]
+    fn parse_error_message() {
+        let html = r#"<div class="alert alert-danger" role="alert">
+            <button type="button" class="close">×</button>
+            <strong>Login failed!</strong> Incorrect username or password.
+        </div>"#;
+        assert_eq!(
+            error_message(html).unwrap(),
+            "Login failed! Incorrect username or password."
+        );
+    }
+}
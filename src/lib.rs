@@ -1,25 +1,73 @@
 #![doc = include_str!("../README.md")]
 
+/// runtime dispatch across sites
+pub mod any;
+/// synchronous clients
+#[cfg(feature = "blocking")]
+pub mod blocking;
+/// layout drift detection
+pub mod canary;
 /// common traits for category
 pub mod category;
 /// common traits for api client
 pub mod client;
+/// commenting on and reporting torrents
+pub mod comment;
+/// categories scraped from any NyaaV2 instance
+pub mod dynamic;
+/// editing and deleting own torrents
+pub mod edit;
 /// nyaa errors
 pub mod error;
-mod extractor;
+/// html parsing of search results
+pub mod extractor;
+/// client-side filters over search results
+pub mod filter;
+mod form;
+#[cfg(test)]
+mod mock;
+/// info hash lookups of torrents from elsewhere
+pub mod lookup;
+/// metrics derived from search results, and sorting by them
+pub mod metrics;
 /// type definitions for api response
 pub mod model;
+/// searches fanned out across categories and search terms
+pub mod multi;
+mod multipart;
 /// nyaa-specific query and client
 pub mod nyaa;
 /// query params
 pub mod query;
+/// html rendering of search results, the inverse of `extractor`
+pub mod renderer;
+/// record/replay transport for tests
+pub mod replay;
+/// `tower::Service` over clients
+#[cfg(feature = "tower")]
+pub mod service;
+/// authenticated sessions
+pub mod session;
 /// sukebei-specific query and client
 pub mod sukebei;
+/// incremental fetching of new uploads
+pub mod sync;
+/// local stand-ins of nyaa for tests
+#[cfg(any(test, feature = "testing"))]
+pub mod testing;
+/// pluggable http transport
+pub mod transport;
+/// torrent uploads
+pub mod upload;
 
-pub use crate::client::Client;
+pub use crate::client::{Client, DynClient};
+pub use crate::edit::TorrentChanges;
 pub use crate::nyaa::{NyaaCategory, NyaaClient};
 pub use crate::query::{Query, QueryBuilder, Sort, SortOrder};
+pub use crate::session::{CookieJar, Session};
 pub use crate::sukebei::{SukebeiCategory, SukebeiClient};
+pub use crate::transport::{ReqwestTransport, Transport};
+pub use crate::upload::{Upload, UploadBuilder};
 
 // re-exports:
 pub use chrono;
@@ -0,0 +1,391 @@
+//! Finding the nyaa page of a magnet link or `.torrent` file by its info
+//! hash, which both sites index in search.
+//!
+//! ```no_run
+//! use nyaa_si::{error::Error, lookup::HashLookup};
+//!
+//! # async fn run() -> nyaa_si::error::Result<()> {
+//! let lookup = HashLookup::new();
+//! match lookup.find_magnet("magnet:?xt=urn:btih:...").await {
+//!     Ok(found) => println!("{} {}", found.url(), found.torrent.title),
+//!     Err(Error::InfoHashNotFound(hash)) => println!("{} is not on nyaa", hash),
+//!     Err(err) => return Err(err),
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use std::fmt::Display;
+use std::str::FromStr;
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::{
+    client::{BoxFuture, Client},
+    error::{Error, Result},
+    model::{Torrent, TorrentId},
+    multi::join_all,
+    nyaa::NyaaClient,
+    query::QueryBuilder,
+    sukebei::SukebeiClient,
+    transport::{ReqwestTransport, Transport},
+};
+
+/// The SHA-1 info hash of a torrent (v1), shown as lowercase hex
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct InfoHash(pub [u8; 20]);
+
+impl InfoHash {
+    /// Read the `urn:btih:` of a magnet link, in hex or base32
+    pub fn from_magnet(magnet: &str) -> Result<Self> {
+        magnet
+            .strip_prefix("magnet:?")
+            .into_iter()
+            .flat_map(|params| params.split('&'))
+            .find_map(|param| param.strip_prefix("xt=urn:btih:"))
+            .ok_or_else(|| {
+                Error::InfoHashError(format!("No btih in {}", magnet))
+            })?
+            .parse()
+    }
+
+    /// Hash the bencoded `info` dictionary of a `.torrent` file
+    pub fn from_torrent_file(file: &[u8]) -> Result<Self> {
+        let info = info_dict(file).ok_or_else(|| {
+            Error::InfoHashError("Invalid torrent file".into())
+        })?;
+        Ok(Self(sha1_smol::Sha1::from(info).digest().bytes()))
+    }
+}
+
+impl FromStr for InfoHash {
+    type Err = Error;
+
+    /// 40 hex or 32 base32 characters
+    fn from_str(s: &str) -> Result<Self> {
+        let bytes = match s.len() {
+            40 => (0..40)
+                .step_by(2)
+                .map(|i| u8::from_str_radix(s.get(i..i + 2)?, 16).ok())
+                .collect::<Option<Vec<_>>>(),
+            32 => base32(s),
+            _ => None,
+        };
+        bytes
+            .and_then(|bytes| bytes.try_into().ok())
+            .map(InfoHash)
+            .ok_or_else(|| {
+                Error::InfoHashError(format!("Invalid info hash {}", s))
+            })
+    }
+}
+
+impl Display for InfoHash {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.0.iter().try_for_each(|byte| write!(f, "{:02x}", byte))
+    }
+}
+
+impl Serialize for InfoHash {
+    fn serialize<S: Serializer>(
+        &self,
+        serializer: S,
+    ) -> std::result::Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for InfoHash {
+    fn deserialize<D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> std::result::Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        s.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+/// RFC 4648 base32, as used by older magnet links
+fn base32(s: &str) -> Option<Vec<u8>> {
+    let mut bytes = Vec::new();
+    let (mut buffer, mut bits) = (0u32, 0);
+    for c in s.bytes() {
+        let value = match c.to_ascii_uppercase() {
+            c @ b'A'..=b'Z' => c - b'A',
+            c @ b'2'..=b'7' => c - b'2' + 26,
+            _ => return None,
+        };
+        buffer = (buffer << 5) | value as u32;
+        bits += 5;
+        if bits >= 8 {
+            bits -= 8;
+            bytes.push((buffer >> bits) as u8);
+        }
+    }
+    Some(bytes)
+}
+
+/// The raw bytes of the top-level `info` value
+fn info_dict(file: &[u8]) -> Option<&[u8]> {
+    let mut pos = 1;
+    if file.first() != Some(&b'd') {
+        return None;
+    }
+    while file.get(pos)? != &b'e' {
+        let key_end = skip_value(file, pos)?;
+        let value_end = skip_value(file, key_end)?;
+        if &file[pos..key_end] == b"4:info" {
+            let info = &file[key_end..value_end];
+            return info.starts_with(b"d").then_some(info);
+        }
+        pos = value_end;
+    }
+    None
+}
+
+/// The position after the bencoded value starting at `pos`
+///
+/// Lists and dictionaries are walked with a depth counter rather than
+/// recursion, so deeply nested input cannot overflow the stack.
+fn skip_value(file: &[u8], mut pos: usize) -> Option<usize> {
+    let mut depth = 0usize;
+    loop {
+        match *file.get(pos)? {
+            b'i' => pos += file[pos..].iter().position(|&b| b == b'e')? + 1,
+            b'l' | b'd' => {
+                depth += 1;
+                pos += 1;
+            }
+            b'e' if depth > 0 => {
+                depth -= 1;
+                pos += 1;
+            }
+            b'0'..=b'9' => {
+                let colon =
+                    pos + file[pos..].iter().position(|&b| b == b':')?;
+                let len: usize = std::str::from_utf8(&file[pos..colon])
+                    .ok()?
+                    .parse()
+                    .ok()?;
+                pos = colon.checked_add(1)?.checked_add(len)?;
+                if pos > file.len() {
+                    return None;
+                }
+            }
+            _ => return None,
+        }
+        if depth == 0 {
+            return Some(pos);
+        }
+    }
+}
+
+/// A torrent found by its info hash
+#[derive(Debug, Clone, PartialEq)]
+pub struct Found {
+    /// `https://nyaa.si` or `https://sukebei.nyaa.si`
+    pub base_url: &'static str,
+    pub id: TorrentId,
+    pub torrent: Torrent,
+}
+
+impl Found {
+    /// The torrent's view page
+    pub fn url(&self) -> String {
+        format!("{}/view/{}", self.base_url, self.id)
+    }
+}
+
+/// Searches nyaa and sukebei for torrents by info hash
+#[derive(Debug, Default)]
+pub struct HashLookup<T = ReqwestTransport> {
+    nyaa: NyaaClient<T>,
+    sukebei: SukebeiClient<T>,
+}
+
+impl HashLookup {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl<T: Transport + Clone> HashLookup<T> {
+    pub fn with_transport(transport: T) -> Self {
+        Self::from_clients(
+            NyaaClient::with_transport(transport.clone()),
+            SukebeiClient::with_transport(transport),
+        )
+    }
+}
+
+impl<T: Transport> HashLookup<T> {
+    pub fn from_clients(
+        nyaa: NyaaClient<T>,
+        sukebei: SukebeiClient<T>,
+    ) -> Self {
+        Self { nyaa, sukebei }
+    }
+
+    /// Search both sites for `hash`, failing with
+    /// [`Error::InfoHashNotFound`] if neither has it
+    ///
+    /// A site that fails is skipped when the other has the torrent, its error
+    /// is only returned when no site found it.
+    #[tracing::instrument(skip(self))]
+    pub async fn find(&self, hash: InfoHash) -> Result<Found> {
+        let search = hash.to_string();
+        let nyaa = QueryBuilder::new().search(search.as_str()).build();
+        let sukebei = QueryBuilder::new().search(search.as_str()).build();
+        let searches: [BoxFuture<'_, Result<Vec<Torrent>>>; 2] = [
+            Box::pin(self.nyaa.get(&nyaa)),
+            Box::pin(self.sukebei.get(&sukebei)),
+        ];
+        let base_urls =
+            [<NyaaClient<T>>::BASE_URL, <SukebeiClient<T>>::BASE_URL];
+
+        let mut error = None;
+        for (base_url, torrents) in
+            base_urls.into_iter().zip(join_all(searches).await)
+        {
+            // a failing site only matters if the other lacks the torrent
+            let torrents = match torrents {
+                Ok(torrents) => torrents,
+                Err(err) => {
+                    error.get_or_insert(err);
+                    continue;
+                }
+            };
+            // search also matches titles, so only trust the same hash
+            let found = torrents.into_iter().find_map(|torrent| {
+                let same = InfoHash::from_magnet(&torrent.magnet_url)
+                    .is_ok_and(|other| other == hash);
+                let id = torrent.id().filter(|_| same)?;
+                Some(Found {
+                    base_url,
+                    id,
+                    torrent,
+                })
+            });
+            if let Some(found) = found {
+                return Ok(found);
+            }
+        }
+        Err(error.unwrap_or(Error::InfoHashNotFound(hash)))
+    }
+
+    pub async fn find_magnet(&self, magnet: &str) -> Result<Found> {
+        self.find(InfoHash::from_magnet(magnet)?).await
+    }
+
+    pub async fn find_torrent_file(&self, file: &[u8]) -> Result<Found> {
+        self.find(InfoHash::from_torrent_file(file)?).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::future::Future;
+
+    use super::*;
+    use crate::mock::torrent;
+    use crate::testing::FakeNyaa;
+    use crate::transport::{Request, Response};
+
+    const HASH: &str = "c12fe1c06bba254a9dc9f519b335aa7c1367a88a";
+
+    #[test]
+    fn parse_info_hashes() {
+        let hex: InfoHash = HASH.parse().unwrap();
+        assert_eq!(hex.to_string(), HASH);
+        let base32: InfoHash =
+            "YEX6DQDLXISUVHOJ6UM3GNNKPQJWPKEK".parse().unwrap();
+        assert_eq!(base32, hex);
+        let magnet = format!("magnet:?dn=show&xt=urn:btih:{}&tr=udp", HASH);
+        assert_eq!(InfoHash::from_magnet(&magnet).unwrap(), hex);
+        assert!(InfoHash::from_magnet("magnet:?dn=show").is_err());
+        assert!("c12fe1c0".parse::<InfoHash>().is_err());
+
+        let file = b"d8:announce3:url4:infod6:lengthi1e4:name1:a\
+                     12:piece lengthi1e6:pieces0:e7:comment2:hie";
+        let hash = InfoHash::from_torrent_file(file).unwrap();
+        // sha1 of the info dictionary alone
+        assert_eq!(
+            hash.to_string(),
+            "cfeccd0345067a0b89dc46ea72186a2cae439ae5"
+        );
+        assert!(InfoHash::from_torrent_file(b"d4:infoi1e").is_err());
+        assert!(InfoHash::from_torrent_file(b"d5:info").is_err());
+
+        let mut nested = b"d4:info".to_vec();
+        nested.resize(2_000_000, b'l');
+        assert!(InfoHash::from_torrent_file(&nested).is_err());
+    }
+
+    #[tokio::test]
+    async fn find_by_hash() {
+        let other = "0000000000000000000000000000000000000000";
+        let server = FakeNyaa::new()
+            .torrent(
+                torrent(1)
+                    .title(format!("Mentions {}", HASH))
+                    .info_hash(other)
+                    .build(),
+            )
+            .torrent(
+                torrent(2)
+                    .title("[Group] Show - 01")
+                    .info_hash(HASH)
+                    .build(),
+            )
+            .start()
+            .unwrap();
+        let lookup = HashLookup::with_transport(server.transport());
+
+        let magnet = format!("magnet:?xt=urn:btih:{}", HASH.to_uppercase());
+        let found = lookup.find_magnet(&magnet).await.unwrap();
+        assert_eq!(found.id, TorrentId(2));
+        assert_eq!(found.url(), "https://nyaa.si/view/2");
+        assert_eq!(found.torrent.title, "[Group] Show - 01");
+
+        let missing = "1111111111111111111111111111111111111111";
+        let err = lookup.find(missing.parse().unwrap()).await.unwrap_err();
+        assert!(
+            matches!(err, Error::InfoHashNotFound(hash) if hash.to_string() == missing)
+        );
+    }
+
+    #[tokio::test]
+    async fn one_site_down() {
+        /// Fails every request to sukebei
+        #[derive(Clone)]
+        struct NyaaOnly<T>(T);
+
+        impl<T: Transport> Transport for NyaaOnly<T> {
+            fn send(
+                &self,
+                request: Request,
+            ) -> impl Future<Output = Result<Response>> + Send {
+                let down = request.uri().host() == Some("sukebei.nyaa.si");
+                let res = self.0.send(request);
+                async move {
+                    if down {
+                        return Err(Error::StatusError(503));
+                    }
+                    res.await
+                }
+            }
+        }
+
+        let server = FakeNyaa::new()
+            .torrent(torrent(1).info_hash(HASH).build())
+            .start()
+            .unwrap();
+        let lookup = HashLookup::with_transport(NyaaOnly(server.transport()));
+
+        let found = lookup.find(HASH.parse().unwrap()).await.unwrap();
+        assert_eq!(found.id, TorrentId(1));
+
+        let missing = "1111111111111111111111111111111111111111";
+        let err = lookup.find(missing.parse().unwrap()).await.unwrap_err();
+        assert!(matches!(err, Error::StatusError(503)));
+    }
+}
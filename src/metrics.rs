@@ -0,0 +1,311 @@
+//! Metrics derived from search results, and client-side sorting by them.
+//!
+//! ```no_run
+//! use nyaa_si::{metrics::SortKey, Client, NyaaClient, QueryBuilder, SortOrder};
+//!
+//! # async fn run() -> nyaa_si::error::Result<()> {
+//! let client = NyaaClient::new();
+//! let query = QueryBuilder::new().search("frieren").build();
+//! let mut torrents = client.get(&query).await?;
+//! SortKey::Health(Default::default()).sort(&mut torrents, SortOrder::Descending);
+//! # Ok(())
+//! # }
+//! ```
+
+use std::fmt::Display;
+use std::str::FromStr;
+use std::sync::OnceLock;
+
+use chrono::{DateTime, Utc};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    error::{Error, Result},
+    model::Torrent,
+    query::SortOrder,
+};
+
+/// Seeders per leecher, counting no leechers as one
+pub fn ratio(torrent: &Torrent) -> f64 {
+    torrent.seeders as f64 / torrent.leechers.max(1) as f64
+}
+
+/// Age at `now` in days, at least one so new uploads do not spike
+pub fn age_days(torrent: &Torrent, now: DateTime<Utc>) -> f64 {
+    let seconds = (now - torrent.date).num_seconds() as f64;
+    (seconds / 86_400.0).max(1.0)
+}
+
+/// Downloads per day since upload
+pub fn downloads_per_day(torrent: &Torrent, now: DateTime<Utc>) -> f64 {
+    torrent.downloads as f64 / age_days(torrent, now)
+}
+
+/// Number of episodes in a batch, and one otherwise
+///
+/// Only ranges that look like a batch count: bracketed like `[01-12]` or
+/// `(01~12)`, prefixed like `E01-E13`, or zero-padded to the same width like
+/// `01 ~ 24`, or any range in a title saying `Batch`. So
+/// `Kaiju No. 8 - 10` or `Show 2 - 05` stay single episodes.
+pub fn episodes(title: &str) -> u32 {
+    static RANGE: OnceLock<Regex> = OnceLock::new();
+    static BATCH: OnceLock<Regex> = OnceLock::new();
+    let range = RANGE.get_or_init(|| {
+        Regex::new(
+            r"(?ix)
+            (?P<open>[\[(]\s*)?
+            (?:\b(?P<ep>ep?)?|(?P<e>e))
+            (?P<first>\d{1,4})\s*(?:-|~|to)\s*(?:ep?)?(?P<last>\d{1,4})\b
+            (?P<close>\s*[\])])?",
+        )
+        .unwrap()
+    });
+    let batch = BATCH.get_or_init(|| Regex::new(r"(?i)\bbatch\b").unwrap());
+    let is_batch = batch.is_match(title);
+
+    range
+        .captures_iter(title)
+        .filter_map(|captures| {
+            let (first, last) = (&captures["first"], &captures["last"]);
+            let bracketed = captures.name("open").is_some()
+                && captures.name("close").is_some();
+            let prefixed =
+                captures.name("ep").is_some() || captures.name("e").is_some();
+            let padded = first.len() > 1
+                && first.len() == last.len()
+                && first.starts_with('0');
+            if !(bracketed || prefixed || padded || is_batch) {
+                return None;
+            }
+            let first: u32 = first.parse().ok()?;
+            let last: u32 = last.parse().ok()?;
+            // years, like `2019-2020`, are not episodes
+            (first < last && last < 1900).then(|| last - first + 1)
+        })
+        .max()
+        .unwrap_or(1)
+}
+
+/// Size in KiB divided by the number of [`episodes`]
+pub fn size_per_episode(torrent: &Torrent) -> f64 {
+    torrent.size.as_kib() / episodes(&torrent.title) as f64
+}
+
+/// Weights of the [`SortKey::Health`] score, which adds up the weighted
+/// logarithms of seeders, [`ratio`], [`downloads_per_day`] and [`age_days`]
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HealthWeights {
+    pub seeders: f64,
+    pub ratio: f64,
+    pub downloads_per_day: f64,
+    /// usually negative, to favour recent uploads
+    pub age: f64,
+}
+
+impl Default for HealthWeights {
+    fn default() -> Self {
+        Self {
+            seeders: 1.0,
+            ratio: 0.5,
+            downloads_per_day: 0.5,
+            age: -0.25,
+        }
+    }
+}
+
+impl HealthWeights {
+    pub fn score(&self, torrent: &Torrent, now: DateTime<Utc>) -> f64 {
+        let log = |value: f64| (1.0 + value).ln();
+        self.seeders * log(torrent.seeders as f64)
+            + self.ratio * log(ratio(torrent))
+            + self.downloads_per_day * log(downloads_per_day(torrent, now))
+            + self.age * log(age_days(torrent, now))
+    }
+}
+
+/// A client-side sort by a derived metric, for any list of results
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SortKey {
+    Ratio,
+    DownloadsPerDay,
+    SizePerEpisode,
+    Health(HealthWeights),
+}
+
+impl SortKey {
+    /// The metric of `torrent` at `now`
+    pub fn value(&self, torrent: &Torrent, now: DateTime<Utc>) -> f64 {
+        match self {
+            SortKey::Ratio => ratio(torrent),
+            SortKey::DownloadsPerDay => downloads_per_day(torrent, now),
+            SortKey::SizePerEpisode => size_per_episode(torrent),
+            SortKey::Health(weights) => weights.score(torrent, now),
+        }
+    }
+
+    /// Sort by the metric as of now, keeping the order of ties
+    pub fn sort(&self, torrents: &mut [Torrent], order: SortOrder) {
+        self.sort_at(torrents, order, Utc::now())
+    }
+
+    /// Sort by the metric as of `now`, keeping the order of ties
+    pub fn sort_at(
+        &self,
+        torrents: &mut [Torrent],
+        order: SortOrder,
+        now: DateTime<Utc>,
+    ) {
+        let mut keyed: Vec<_> = torrents
+            .iter()
+            .map(|torrent| self.value(torrent, now))
+            .zip(torrents.iter().cloned())
+            .collect();
+        keyed.sort_by(|(a, _), (b, _)| match order {
+            SortOrder::Asecending => a.total_cmp(b),
+            SortOrder::Descending => b.total_cmp(a),
+        });
+        for (slot, (_, torrent)) in torrents.iter_mut().zip(keyed) {
+            *slot = torrent;
+        }
+    }
+}
+
+impl FromStr for SortKey {
+    type Err = Error;
+
+    /// `ratio`, `downloads-per-day`, `size-per-episode` or `health`, with
+    /// default weights
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "ratio" => Ok(SortKey::Ratio),
+            "downloads-per-day" => Ok(SortKey::DownloadsPerDay),
+            "size-per-episode" => Ok(SortKey::SizePerEpisode),
+            "health" => Ok(SortKey::Health(HealthWeights::default())),
+            _ => Err(Error::ConfigError(format!("Invalid sort key {}", s))),
+        }
+    }
+}
+
+impl Display for SortKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SortKey::Ratio => write!(f, "ratio"),
+            SortKey::DownloadsPerDay => write!(f, "downloads-per-day"),
+            SortKey::SizePerEpisode => write!(f, "size-per-episode"),
+            SortKey::Health(_) => write!(f, "health"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock;
+    use crate::model::Size;
+
+    fn torrent(
+        title: &str,
+        (seeders, leechers, downloads): (u32, u32, u32),
+        size: Size,
+        days_old: i64,
+    ) -> Torrent {
+        mock::torrent(seeders)
+            .title(title)
+            .seeders(seeders)
+            .leechers(leechers)
+            .downloads(downloads)
+            .size(size)
+            .date(now() - chrono::Duration::days(days_old))
+            .build()
+    }
+
+    fn now() -> DateTime<Utc> {
+        DateTime::from_timestamp(1_700_000_000, 0).unwrap()
+    }
+
+    fn titles(torrents: &[Torrent]) -> Vec<&str> {
+        torrents
+            .iter()
+            .map(|torrent| torrent.title.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn count_episodes() {
+        assert_eq!(episodes("[Group] Show (01-12) [1080p]"), 12);
+        assert_eq!(episodes("[Group] Show - 01 ~ 24 [BD]"), 24);
+        assert_eq!(episodes("Show S01E01-E13 1080p"), 13);
+        assert_eq!(episodes("[Group] Show - 05 [1920x1080]"), 1);
+        assert_eq!(episodes("Show (2019-2020) Complete"), 1);
+        assert_eq!(episodes("[Group] Show Batch 1-12 [1080p]"), 12);
+    }
+
+    #[test]
+    fn single_episodes() {
+        assert_eq!(episodes("[Group] Kaiju No. 8 - 10 [1080p]"), 1);
+        assert_eq!(episodes("[Group] Show 2 - 05 [720p]"), 1);
+        assert_eq!(episodes("[Group] Mob Psycho 100 - 12 [1080p]"), 1);
+        assert_eq!(episodes("[Group] Show S2 - 11 (1080p)"), 1);
+    }
+
+    #[test]
+    fn sort_by_metrics() {
+        let mut torrents = vec![
+            torrent("old", (100, 50, 3000), Size::GB(12.0), 300),
+            torrent("new", (30, 0, 90), Size::GB(1.0), 3),
+            torrent("batch 01-12", (60, 30, 1200), Size::GB(6.0), 30),
+        ];
+
+        SortKey::Ratio.sort_at(&mut torrents, SortOrder::Descending, now());
+        assert_eq!(titles(&torrents), ["new", "old", "batch 01-12"]);
+
+        SortKey::DownloadsPerDay.sort_at(
+            &mut torrents,
+            SortOrder::Descending,
+            now(),
+        );
+        assert_eq!(titles(&torrents), ["batch 01-12", "new", "old"]);
+
+        SortKey::SizePerEpisode.sort_at(
+            &mut torrents,
+            SortOrder::Asecending,
+            now(),
+        );
+        assert_eq!(titles(&torrents), ["batch 01-12", "new", "old"]);
+
+        let seeders_only = HealthWeights {
+            seeders: 1.0,
+            ratio: 0.0,
+            downloads_per_day: 0.0,
+            age: 0.0,
+        };
+        SortKey::Health(seeders_only).sort_at(
+            &mut torrents,
+            SortOrder::Descending,
+            now(),
+        );
+        assert_eq!(titles(&torrents), ["old", "batch 01-12", "new"]);
+        SortKey::Health(HealthWeights::default()).sort_at(
+            &mut torrents,
+            SortOrder::Descending,
+            now(),
+        );
+        assert_eq!(titles(&torrents), ["new", "batch 01-12", "old"]);
+    }
+
+    #[test]
+    fn parse_sort_keys() {
+        for key in ["ratio", "downloads-per-day", "size-per-episode", "health"]
+        {
+            assert_eq!(key.parse::<SortKey>().unwrap().to_string(), key);
+        }
+        assert!("seeders".parse::<SortKey>().is_err());
+
+        let weights: HealthWeights =
+            serde_json::from_str(r#"{"age": -1.0}"#).unwrap();
+        assert_eq!(weights.seeders, 1.0);
+        assert_eq!(weights.age, -1.0);
+    }
+}
@@ -0,0 +1,115 @@
+//! Fixtures shared by the unit tests.
+
+pub use crate::testing::{MockResponse, MockServer};
+
+use chrono::{DateTime, Utc};
+
+use crate::category::Category;
+use crate::model::{Size, Torrent};
+use crate::replay::{ReplayMode, ReplayTransport};
+
+/// Saved responses for [`crate::replay::ReplayTransport`].
+pub const FIXTURES: &str =
+    concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures");
+
+/// Replay [`FIXTURES`] offline, only going to the live site with
+/// `NYAA_SI_REPLAY=record`.
+pub fn fixtures() -> ReplayTransport {
+    let mode = match ReplayMode::from_env() {
+        ReplayMode::Record => ReplayMode::Record,
+        _ => ReplayMode::Replay,
+    };
+    ReplayTransport::new(FIXTURES).mode(mode)
+}
+
+/// A search result page with a single torrent.
+pub const LISTING: &str = r#"<table><tbody><tr class="default">
+    <td><a href="/?c=1_2" title="Anime - English-translated"></a></td>
+    <td colspan="2"><a href="/view/1" title="[Group] Show - 01">[Group] Show - 01</a></td>
+    <td class="text-center">
+        <a href="/download/1.torrent"><i class="fa fa-fw fa-download"></i></a>
+        <a href="magnet:?xt=urn:btih:0123456789abcdef0123456789abcdef01234567"><i class="fa fa-fw fa-magnet"></i></a>
+    </td>
+    <td class="text-center">1.2 GiB</td>
+    <td class="text-center" data-timestamp="1700000000">2023-11-14 22:13</td>
+    <td class="text-center">10</td>
+    <td class="text-center">2</td>
+    <td class="text-center">300</td>
+</tr></tbody></table>"#;
+
+/// A torrent on nyaa.si with `id`, uploaded `id` seconds after
+/// 2023-11-14 22:13, for the tests to adjust what they look at.
+pub fn torrent(id: u32) -> TorrentBuilder {
+    TorrentBuilder(Torrent {
+        title: format!("[Group] Show - {:02}", id),
+        link: format!("https://nyaa.si/download/{}.torrent", id),
+        magnet_url: format!("magnet:?xt=urn:btih:{:040x}&dn=show", id),
+        date: DateTime::from_timestamp(1_700_000_000 + id as i64, 0).unwrap(),
+        seeders: 10,
+        leechers: 2,
+        downloads: 300,
+        size: Size::GB(1.2),
+        category: Some("1_2".into()),
+        uploader: None,
+    })
+}
+
+#[derive(Debug, Clone)]
+pub struct TorrentBuilder(Torrent);
+
+impl TorrentBuilder {
+    pub fn build(self) -> Torrent {
+        self.0
+    }
+
+    pub fn title<S: Into<String>>(mut self, title: S) -> Self {
+        self.0.title = title.into();
+        self
+    }
+
+    /// A magnet link of the hex info hash `hash`
+    pub fn info_hash(mut self, hash: &str) -> Self {
+        self.0.magnet_url = format!("magnet:?xt=urn:btih:{}&dn=show", hash);
+        self
+    }
+
+    pub fn magnet_url<S: Into<String>>(mut self, magnet_url: S) -> Self {
+        self.0.magnet_url = magnet_url.into();
+        self
+    }
+
+    pub fn date(mut self, date: DateTime<Utc>) -> Self {
+        self.0.date = date;
+        self
+    }
+
+    pub fn seeders(mut self, seeders: u32) -> Self {
+        self.0.seeders = seeders;
+        self
+    }
+
+    pub fn leechers(mut self, leechers: u32) -> Self {
+        self.0.leechers = leechers;
+        self
+    }
+
+    pub fn downloads(mut self, downloads: u32) -> Self {
+        self.0.downloads = downloads;
+        self
+    }
+
+    pub fn category<C: Category>(mut self, category: C) -> Self {
+        self.0.category = Some(category.to_string());
+        self
+    }
+
+    pub fn uploader(mut self, uploader: &str) -> Self {
+        self.0.uploader = Some(uploader.into());
+        self
+    }
+
+    pub fn size(mut self, size: Size) -> Self {
+        self.0.size = size;
+        self
+    }
+}
@@ -2,6 +2,7 @@ use serde::{Deserialize, Serialize};
 use std::cmp::Ordering;
 use std::{fmt::Display, str::FromStr};
 
+use crate::category::Category;
 use crate::error::Error;
 
 /// file size
@@ -51,23 +52,88 @@ impl Display for Size {
     }
 }
 
+impl Size {
+    /// The size in KiB
+    pub fn as_kib(&self) -> f64 {
+        match self {
+            Size::KB(value) => *value as f64,
+            Size::MB(value) => *value as f64 * 1024.0,
+            Size::GB(value) => *value as f64 * 1024.0 * 1024.0,
+            Size::TB(value) => *value as f64 * 1024.0 * 1024.0 * 1024.0,
+        }
+    }
+}
+
 impl PartialOrd for Size {
     fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-        let lhs_in_kb = match self {
-            Size::KB(value) => *value,
-            Size::MB(value) => value * 1024.0,
-            Size::GB(value) => value * 1024.0 * 1024.0,
-            Size::TB(value) => value * 1024.0 * 1024.0 * 1024.0,
-        };
+        self.as_kib().partial_cmp(&other.as_kib())
+    }
+}
 
-        let rhs_in_kb = match other {
-            Size::KB(value) => *value,
-            Size::MB(value) => value * 1024.0,
-            Size::GB(value) => value * 1024.0 * 1024.0,
-            Size::TB(value) => value * 1024.0 * 1024.0 * 1024.0,
+/// id of a torrent, as in `/view/{id}`
+#[derive(
+    Debug,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Hash,
+    Clone,
+    Copy,
+    Serialize,
+    Deserialize,
+)]
+pub struct TorrentId(pub u32);
+
+impl TorrentId {
+    /// Parse the id out of a `/view/{id}` or `/download/{id}.torrent` url.
+    pub fn from_url(url: &str) -> Option<Self> {
+        let path = url.split(['?', '#']).next()?;
+        let mut segments = path.rsplit('/');
+        let last = segments.next()?;
+        let id = match segments.next()? {
+            "download" => last.strip_suffix(".torrent")?,
+            "view" => last,
+            _ => return None,
         };
+        id.parse().ok().map(TorrentId)
+    }
+}
+
+impl FromStr for TorrentId {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        s.parse().map(TorrentId).map_err(|_| {
+            Error::SelectorError(format!("Invalid torrent id: {}", s))
+        })
+    }
+}
+
+impl Display for TorrentId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
 
-        lhs_in_kb.partial_cmp(&rhs_in_kb)
+/// id of a comment on a torrent's page
+#[derive(
+    Debug,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Hash,
+    Clone,
+    Copy,
+    Serialize,
+    Deserialize,
+)]
+pub struct CommentId(pub u32);
+
+impl Display for CommentId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
     }
 }
 
@@ -82,6 +148,31 @@ pub struct Torrent {
     pub leechers: u32,
     pub downloads: u32,
     pub size: Size,
+    /// category code, e.g. `1_2`, `None` if the listing did not show one
+    #[serde(default)]
+    pub category: Option<String>,
+    /// name of the uploader, `None` for anonymous uploads and for listings,
+    /// which don't show it, see `NyaaClient::uploader`
+    #[serde(default)]
+    pub uploader: Option<String>,
+}
+
+impl Torrent {
+    /// id of the torrent, parsed from its download link
+    pub fn id(&self) -> Option<TorrentId> {
+        TorrentId::from_url(&self.link)
+    }
+
+    /// The torrent's category among those of site `C`
+    pub fn category_of<C: Category>(&self) -> Option<C> {
+        self.category.as_deref()?.parse().ok()
+    }
+
+    /// Whether the torrent is in `category` or one of its sub-categories
+    pub fn in_category<C: Category>(&self, category: &C) -> bool {
+        self.category_of()
+            .is_some_and(|own: C| category.contains(&own))
+    }
 }
 
 impl PartialEq for Torrent {
@@ -93,6 +184,7 @@ impl PartialEq for Torrent {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::mock;
 
     #[test]
     fn parse_size_units() {
@@ -121,4 +213,32 @@ mod tests {
         assert!(Size::GB(33.4) < Size::GB(44.5));
         assert!(Size::TB(33.4) < Size::TB(44.5));
     }
+
+    #[test]
+    fn parse_torrent_id() {
+        assert_eq!(
+            TorrentId::from_url("https://nyaa.si/download/1234.torrent"),
+            Some(TorrentId(1234))
+        );
+        assert_eq!(TorrentId::from_url("/view/42#com-1"), Some(TorrentId(42)));
+        assert_eq!(TorrentId::from_url("/view/abc"), None);
+        assert_eq!(TorrentId::from_url("/user/42"), None);
+    }
+
+    #[test]
+    fn torrent_category() {
+        use crate::NyaaCategory;
+
+        let torrent = mock::torrent(1).category(NyaaCategory::AnimeRaw).build();
+        assert_eq!(torrent.category_of(), Some(NyaaCategory::AnimeRaw));
+        assert!(torrent.in_category(&NyaaCategory::Anime));
+        assert!(torrent.in_category(&NyaaCategory::All));
+        assert!(!torrent.in_category(&NyaaCategory::Audio));
+
+        let unknown = Torrent {
+            category: None,
+            ..torrent
+        };
+        assert!(!unknown.in_category(&NyaaCategory::All));
+    }
 }
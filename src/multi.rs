@@ -0,0 +1,263 @@
+//! Searches fanned out across several categories or search terms, since
+//! nyaa only takes one of each per request.
+//!
+//! ```no_run
+//! use nyaa_si::{multi::MultiQuery, NyaaCategory, NyaaClient, QueryBuilder};
+//!
+//! # async fn run() -> nyaa_si::error::Result<()> {
+//! let client = NyaaClient::new();
+//! let query = QueryBuilder::new().search("frieren").build();
+//! let torrents = MultiQuery::new(query)
+//!     .categories([
+//!         NyaaCategory::AnimeEnglishTranslated,
+//!         NyaaCategory::LiveActionEnglishTranslated,
+//!     ])
+//!     .get(&client)
+//!     .await?;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::cmp::Ordering;
+use std::collections::HashSet;
+use std::future::{poll_fn, Future};
+use std::task::Poll;
+
+use crate::{
+    category::Category,
+    client::DynClient,
+    error::Result,
+    model::Torrent,
+    query::{Query, Sort, SortOrder},
+};
+
+/// A query run once per category and search term
+#[derive(Debug, Clone, PartialEq)]
+pub struct MultiQuery<C> {
+    query: Query<C>,
+    categories: Vec<C>,
+    searches: Vec<String>,
+}
+
+impl<C: Category> MultiQuery<C> {
+    /// Fan out `query`, whose page, sort and filter every search shares
+    pub fn new(query: Query<C>) -> Self {
+        Self {
+            query,
+            categories: Vec::new(),
+            searches: Vec::new(),
+        }
+    }
+
+    /// Search each of `categories` instead of the query's category
+    pub fn categories<I: IntoIterator<Item = C>>(
+        mut self,
+        categories: I,
+    ) -> Self {
+        self.categories = categories.into_iter().collect();
+        self
+    }
+
+    /// Search each of `searches` instead of the query's search
+    pub fn searches<I, S>(mut self, searches: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.searches = searches.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// One query per category and search term
+    pub fn queries(&self) -> Vec<Query<C>> {
+        let categories = match self.categories.as_slice() {
+            [] => std::slice::from_ref(self.query.category()),
+            categories => categories,
+        };
+        let searches = match self.searches.as_slice() {
+            [] => vec![self.query.search()],
+            searches => searches.iter().map(String::as_str).collect(),
+        };
+        categories
+            .iter()
+            .flat_map(|category| {
+                searches.iter().map(|search| {
+                    self.query
+                        .to_builder()
+                        .category(*category)
+                        .search(*search)
+                        .build()
+                })
+            })
+            .collect()
+    }
+
+    /// Send every query concurrently and [`merge`] the results, failing if
+    /// any of them fails
+    #[tracing::instrument(skip(self, client))]
+    pub async fn get<S>(&self, client: &S) -> Result<Vec<Torrent>>
+    where
+        S: DynClient<C> + ?Sized,
+    {
+        let queries = self.queries();
+        let results = join_all(queries.iter().map(|query| client.get(query)))
+            .await
+            .into_iter()
+            .collect::<Result<Vec<_>>>()?;
+        Ok(merge(results, self.query.sort(), self.query.sort_order()))
+    }
+}
+
+/// Concatenate `results`, keep the first of each torrent id (or link, for
+/// torrents without one) and sort them
+///
+/// The order of [`Sort::Comments`] cannot be recreated, as search results do
+/// not carry comment counts, so it keeps the order of `results`.
+pub fn merge<I>(results: I, sort: Sort, order: SortOrder) -> Vec<Torrent>
+where
+    I: IntoIterator<Item = Vec<Torrent>>,
+{
+    let mut seen = HashSet::new();
+    let mut torrents: Vec<_> = results
+        .into_iter()
+        .flatten()
+        .filter(|torrent| match torrent.id() {
+            Some(id) => seen.insert(id.to_string()),
+            None => seen.insert(torrent.link.clone()),
+        })
+        .collect();
+    torrents.sort_by(|a, b| {
+        let ordering = compare(a, b, sort);
+        match order {
+            SortOrder::Asecending => ordering,
+            SortOrder::Descending => ordering.reverse(),
+        }
+    });
+    torrents
+}
+
+fn compare(a: &Torrent, b: &Torrent, sort: Sort) -> Ordering {
+    match sort {
+        Sort::Comments => Ordering::Equal,
+        Sort::Size => a.size.partial_cmp(&b.size).unwrap_or(Ordering::Equal),
+        Sort::Date => a.date.cmp(&b.date).then_with(|| a.id().cmp(&b.id())),
+        Sort::Seeders => a.seeders.cmp(&b.seeders),
+        Sort::Leechers => a.leechers.cmp(&b.leechers),
+        Sort::Downloads => a.downloads.cmp(&b.downloads),
+    }
+}
+
+/// Poll every future until all are done, keeping their order
+pub(crate) async fn join_all<F: Future>(
+    futures: impl IntoIterator<Item = F>,
+) -> Vec<F::Output> {
+    let mut futures: Vec<_> = futures.into_iter().map(Box::pin).collect();
+    let mut outputs: Vec<_> = futures.iter().map(|_| None).collect();
+    poll_fn(|cx| {
+        let mut done = true;
+        for (future, output) in futures.iter_mut().zip(&mut outputs) {
+            if output.is_none() {
+                match future.as_mut().poll(cx) {
+                    Poll::Ready(value) => *output = Some(value),
+                    Poll::Pending => done = false,
+                }
+            }
+        }
+        if done {
+            Poll::Ready(())
+        } else {
+            Poll::Pending
+        }
+    })
+    .await;
+    outputs.into_iter().map(Option::unwrap).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock;
+    use crate::testing::{FakeNyaa, FakeTorrent};
+    use crate::{NyaaCategory, NyaaClient, QueryBuilder};
+
+    fn torrent(id: u32, title: &str, seeders: u32) -> Torrent {
+        mock::torrent(id).title(title).seeders(seeders).build()
+    }
+
+    #[test]
+    fn fan_out_queries() {
+        let query = QueryBuilder::new()
+            .search("frieren")
+            .page(2)
+            .category(NyaaCategory::Anime)
+            .build();
+        let multi = MultiQuery::new(query.clone());
+        assert_eq!(multi.queries(), [query]);
+
+        let queries = multi
+            .categories([NyaaCategory::AnimeRaw, NyaaCategory::LiveActionRaw])
+            .searches(["frieren", "sousou no frieren"])
+            .queries();
+        assert_eq!(queries.len(), 4);
+        assert_eq!(queries[1].search(), "sousou no frieren");
+        assert_eq!(queries[2].category(), &NyaaCategory::LiveActionRaw);
+        assert!(queries.iter().all(|query| query.page() == 2));
+    }
+
+    #[test]
+    fn merge_dedupes_and_sorts() {
+        let merged = merge(
+            [
+                vec![torrent(1, "a", 5), torrent(2, "b", 1)],
+                vec![torrent(3, "c", 9), torrent(1, "a", 5)],
+            ],
+            Sort::Seeders,
+            SortOrder::Descending,
+        );
+        let ids: Vec<_> = merged.iter().map(|t| t.id().unwrap().0).collect();
+        assert_eq!(ids, [3, 1, 2]);
+
+        let merged = merge([merged], Sort::Date, SortOrder::Asecending);
+        let ids: Vec<_> = merged.iter().map(|t| t.id().unwrap().0).collect();
+        assert_eq!(ids, [1, 2, 3]);
+    }
+
+    #[tokio::test]
+    async fn search_several_categories() {
+        let server = FakeNyaa::new()
+            .torrent(
+                FakeTorrent::new(torrent(1, "Frieren - 01 [1080p]", 10))
+                    .category(NyaaCategory::AnimeEnglishTranslated),
+            )
+            .torrent(
+                FakeTorrent::new(torrent(2, "Frieren Live - 01", 30))
+                    .category(NyaaCategory::LiveActionEnglishTranslated),
+            )
+            .torrent(
+                FakeTorrent::new(torrent(3, "Frieren - 01 [raw]", 20))
+                    .category(NyaaCategory::AnimeRaw),
+            )
+            .start()
+            .unwrap();
+        let client = NyaaClient::with_transport(server.transport());
+        let query = QueryBuilder::new().search("frieren").build();
+
+        let torrents = MultiQuery::new(query.clone())
+            .categories([
+                NyaaCategory::AnimeEnglishTranslated,
+                NyaaCategory::LiveActionEnglishTranslated,
+            ])
+            .get(&client)
+            .await
+            .unwrap();
+        let ids: Vec<_> = torrents.iter().map(|t| t.id().unwrap().0).collect();
+        assert_eq!(ids, [2, 1]);
+
+        let torrents = MultiQuery::new(query)
+            .searches(["frieren", "01"])
+            .get(&client)
+            .await
+            .unwrap();
+        assert_eq!(torrents.len(), 3);
+    }
+}
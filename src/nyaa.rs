@@ -1,14 +1,28 @@
 use std::fmt::Display;
-use tokio::task::spawn_blocking;
+use std::str::FromStr;
+use std::sync::Arc;
+
+use http::Method;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use tracing::{event, Level};
 
 use crate::{
-    category::Category, client::Client, error::Result, extractor::extract,
-    model::Torrent, query::Query,
+    canary::{self, HealthReport},
+    category::{self, Category},
+    client::Client,
+    error::{Error, Result},
+    extractor::{extract_owned, fetch_uploader, Extractor},
+    model::{Torrent, TorrentId},
+    query::Query,
+    session::{CookieJar, Session},
+    transport::{
+        self, RequestBuilderExt, ReqwestTransport, ResponseExt, Transport,
+    },
 };
 
-#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq, Default)]
 pub enum NyaaCategory {
+    #[default]
     All,
     Anime,
     AnimeMusicVideo,
@@ -35,50 +49,173 @@ pub enum NyaaCategory {
     SoftwareGames,
 }
 
+/// Code, name and description of each category, in declaration order
+const CATEGORIES: [(&str, &str, &str); 24] = [
+    ("0_0", "All categories", "Everything on the site"),
+    ("1_0", "Anime", "Anime in any language or format"),
+    (
+        "1_1",
+        "Anime - Anime Music Video",
+        "Fan-made music videos of anime footage",
+    ),
+    (
+        "1_2",
+        "Anime - English-translated",
+        "Anime with English subtitles or dubs",
+    ),
+    (
+        "1_3",
+        "Anime - Non-English-translated",
+        "Anime translated to languages other than English",
+    ),
+    ("1_4", "Anime - Raw", "Anime without translation"),
+    ("2_0", "Audio", "Music, soundtracks and other audio"),
+    (
+        "2_1",
+        "Audio - Lossless",
+        "Audio in lossless formats such as FLAC",
+    ),
+    (
+        "2_2",
+        "Audio - Lossy",
+        "Audio in lossy formats such as MP3 or AAC",
+    ),
+    ("3_0", "Literature", "Manga, light novels and other books"),
+    (
+        "3_1",
+        "Literature - English-translated",
+        "Literature translated to English",
+    ),
+    (
+        "3_2",
+        "Literature - Non-English-translated",
+        "Literature translated to languages other than English",
+    ),
+    ("3_3", "Literature - Raw", "Literature without translation"),
+    (
+        "4_0",
+        "Live Action",
+        "Dramas, films and other live action video",
+    ),
+    (
+        "4_1",
+        "Live Action - English-translated",
+        "Live action with English subtitles or dubs",
+    ),
+    (
+        "4_2",
+        "Live Action - Idol/Promotional Video",
+        "Idol and promotional videos",
+    ),
+    (
+        "4_3",
+        "Live Action - Non-English-translated",
+        "Live action translated to languages other than English",
+    ),
+    (
+        "4_4",
+        "Live Action - Raw",
+        "Live action without translation",
+    ),
+    ("5_0", "Pictures", "Artwork and photos"),
+    (
+        "5_1",
+        "Pictures - Graphics",
+        "Artwork, scans and wallpapers",
+    ),
+    ("5_2", "Pictures - Photos", "Photos and photobooks"),
+    ("6_0", "Software", "Applications and games"),
+    ("6_1", "Software - Applications", "Applications and tools"),
+    ("6_2", "Software - Games", "Games and visual novels"),
+];
+
+impl NyaaCategory {
+    /// Every category, starting with the default `All`
+    pub const ALL: &'static [Self] = &[
+        Self::All,
+        Self::Anime,
+        Self::AnimeMusicVideo,
+        Self::AnimeEnglishTranslated,
+        Self::AnimeNonEnglishTranslated,
+        Self::AnimeRaw,
+        Self::Audio,
+        Self::AudioLossless,
+        Self::AudioLossy,
+        Self::Literature,
+        Self::LiteratureEnglishTranslated,
+        Self::LiteratureNonEnglishTranslated,
+        Self::LiteratureRaw,
+        Self::LiveAction,
+        Self::LiveActionEnglishTranslated,
+        Self::LiveActionIdol,
+        Self::LiveActionNonEnglishTranslated,
+        Self::LiveActionRaw,
+        Self::Pictures,
+        Self::PicturesGraphics,
+        Self::PicturesPhotos,
+        Self::Software,
+        Self::SoftwareApplications,
+        Self::SoftwareGames,
+    ];
+}
+
 impl Display for NyaaCategory {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        match self {
-            Self::All => write!(f, "0_0"),
-            Self::Anime => write!(f, "1_0"),
-            Self::AnimeMusicVideo => write!(f, "1_1"),
-            Self::AnimeEnglishTranslated => write!(f, "1_2"),
-            Self::AnimeNonEnglishTranslated => write!(f, "1_3"),
-            Self::AnimeRaw => write!(f, "1_4"),
-            Self::Audio => write!(f, "2_0"),
-            Self::AudioLossless => write!(f, "2_1"),
-            Self::AudioLossy => write!(f, "2_2"),
-            Self::Literature => write!(f, "3_0"),
-            Self::LiteratureEnglishTranslated => write!(f, "3_1"),
-            Self::LiteratureNonEnglishTranslated => write!(f, "3_2"),
-            Self::LiteratureRaw => write!(f, "3_3"),
-            Self::LiveAction => write!(f, "4_0"),
-            Self::LiveActionEnglishTranslated => write!(f, "4_1"),
-            Self::LiveActionIdol => write!(f, "4_2"),
-            Self::LiveActionNonEnglishTranslated => write!(f, "4_3"),
-            Self::LiveActionRaw => write!(f, "4_4"),
-            Self::Pictures => write!(f, "5_0"),
-            Self::PicturesGraphics => write!(f, "5_1"),
-            Self::PicturesPhotos => write!(f, "5_2"),
-            Self::Software => write!(f, "6_0"),
-            Self::SoftwareApplications => write!(f, "6_1"),
-            Self::SoftwareGames => write!(f, "6_2"),
-        }
+        f.write_str(self.code())
     }
 }
 
-impl Default for NyaaCategory {
-    fn default() -> Self {
-        Self::All
+impl FromStr for NyaaCategory {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        category::parse(s)
     }
 }
 
-impl Category for NyaaCategory {}
+impl Serialize for NyaaCategory {
+    fn serialize<S: Serializer>(
+        &self,
+        serializer: S,
+    ) -> std::result::Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.code())
+    }
+}
+
+impl<'de> Deserialize<'de> for NyaaCategory {
+    fn deserialize<D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> std::result::Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        s.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+impl Category for NyaaCategory {
+    fn all() -> Vec<Self> {
+        Self::ALL.to_vec()
+    }
+
+    fn code(&self) -> &'static str {
+        CATEGORIES[*self as usize].0
+    }
+
+    fn name(&self) -> &'static str {
+        CATEGORIES[*self as usize].1
+    }
+
+    fn description(&self) -> &'static str {
+        CATEGORIES[*self as usize].2
+    }
+}
 
 pub type NyaaQuery = Query<NyaaCategory>;
+pub type NyaaSession<T = ReqwestTransport> = Session<NyaaCategory, T>;
 
 #[derive(Debug, Default)]
-pub struct NyaaClient {
-    inner: reqwest::Client,
+pub struct NyaaClient<T = ReqwestTransport> {
+    transport: T,
+    extractor: Arc<Extractor>,
 }
 
 impl NyaaClient {
@@ -87,12 +224,75 @@ impl NyaaClient {
     }
 }
 
-impl Client<NyaaCategory> for NyaaClient {
+impl<T: Transport> NyaaClient<T> {
+    /// Send requests through a custom transport
+    pub fn with_transport(transport: T) -> Self {
+        Self {
+            transport,
+            extractor: Arc::default(),
+        }
+    }
+
+    /// Parse results with a custom extractor, e.g. to follow markup changes
+    pub fn with_extractor<E: Into<Arc<Extractor>>>(
+        mut self,
+        extractor: E,
+    ) -> Self {
+        self.extractor = extractor.into();
+        self
+    }
+
+    /// Fetch a search that should have results and check its page still has
+    /// the layout the extractor expects
+    pub async fn check_layout(
+        &self,
+        query: &Query<NyaaCategory>,
+    ) -> Result<HealthReport> {
+        canary::check(&self.transport, &self.extractor, Self::BASE_URL, query)
+            .await
+    }
+
+    /// Fetch who uploaded torrent `id` from its view page, `None` for
+    /// anonymous uploads, e.g. to fill [`Torrent::uploader`] before filtering
+    pub async fn uploader(&self, id: TorrentId) -> Result<Option<String>> {
+        fetch_uploader(&self.transport, Self::BASE_URL, id).await
+    }
+}
+
+impl<T: Transport + Clone> NyaaClient<T> {
+    /// Log in to nyaa.si and return the authenticated session
+    pub async fn login(
+        &self,
+        username: &str,
+        password: &str,
+    ) -> Result<NyaaSession<T>> {
+        let session = NyaaSession::with_transport(
+            self.transport.clone(),
+            Self::BASE_URL,
+            CookieJar::new(),
+        )
+        .with_extractor(self.extractor.clone());
+        session.login(username, password).await?;
+        Ok(session)
+    }
+
+    /// Resume a session from previously saved cookies
+    pub fn resume(&self, cookies: CookieJar) -> NyaaSession<T> {
+        NyaaSession::with_transport(
+            self.transport.clone(),
+            Self::BASE_URL,
+            cookies,
+        )
+        .with_extractor(self.extractor.clone())
+    }
+}
+
+impl<T: Transport> Client<NyaaCategory> for NyaaClient<T> {
     const BASE_URL: &'static str = "https://nyaa.si";
 
     /// Send a query to the api
     ///
-    ///```
+    ///```no_run
     #[doc = include_str!("../examples/custom_query.rs")]
     ///```
     #[tracing::instrument(skip(self))]
@@ -101,27 +301,42 @@ impl Client<NyaaCategory> for NyaaClient {
 
         event!(Level::DEBUG, "url = {}", url);
 
-        let res = self.inner.get(url).send().await?.text().await?;
+        let res = self
+            .transport
+            .send(transport::request(Method::GET, &url).empty()?)
+            .await?;
+        if !res.status().is_success() {
+            return Err(Error::StatusError(res.status().as_u16()));
+        }
 
-        spawn_blocking(move || extract(&res, Self::BASE_URL)).await?
+        extract_owned(
+            self.extractor.clone(),
+            res.text(),
+            Self::BASE_URL.to_string(),
+        )
+        .await
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::mock::fixtures;
     use crate::query::QueryBuilder;
     use crate::query::Sort;
 
     #[tokio::test]
     async fn test_get() {
-        let client = NyaaClient::new();
+        let client = NyaaClient::with_transport(fixtures());
         let query = QueryBuilder::new()
             .search("frieren")
             .sort(Sort::Downloads)
             .build();
         let res = client.get(&query).await.unwrap();
 
-        println!("{:#?}", res.get(0).unwrap());
+        let first = res.first().unwrap();
+        assert!(first.title.to_lowercase().contains("frieren"));
+        assert!(first.link.starts_with(<NyaaClient>::BASE_URL));
+        assert!(res.windows(2).all(|w| w[0].downloads >= w[1].downloads));
     }
 }
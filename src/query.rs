@@ -1,7 +1,28 @@
 use std::{fmt::Display, str::FromStr};
 
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
 use crate::category::Category;
 
+/// Serialize as the `Display` code
+fn serialize_code<T: Display, S: Serializer>(
+    value: &T,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    serializer.collect_str(value)
+}
+
+/// Deserialize with `FromStr`
+fn deserialize_code<'de, T, D>(deserializer: D) -> Result<T, D::Error>
+where
+    T: FromStr,
+    T::Err: Display,
+    D: Deserializer<'de>,
+{
+    let s = String::deserialize(deserializer)?;
+    s.parse().map_err(serde::de::Error::custom)
+}
+
 #[derive(Debug, PartialEq, Eq, Clone, Copy, Hash, Default)]
 pub enum SortOrder {
     Asecending,
@@ -20,6 +41,23 @@ impl FromStr for SortOrder {
     }
 }
 
+impl Serialize for SortOrder {
+    fn serialize<S: Serializer>(
+        &self,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serialize_code(self, serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for SortOrder {
+    fn deserialize<D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Self, D::Error> {
+        deserialize_code(deserializer)
+    }
+}
+
 impl Display for SortOrder {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
@@ -46,7 +84,7 @@ impl FromStr for Sort {
         match s {
             "comments" => Ok(Sort::Comments),
             "size" => Ok(Sort::Size),
-            "date" => Ok(Sort::Date),
+            "date" | "id" => Ok(Sort::Date),
             "seeders" => Ok(Sort::Seeders),
             "leechers" => Ok(Sort::Leechers),
             "downloads" => Ok(Sort::Downloads),
@@ -55,6 +93,23 @@ impl FromStr for Sort {
     }
 }
 
+impl Serialize for Sort {
+    fn serialize<S: Serializer>(
+        &self,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serialize_code(self, serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Sort {
+    fn deserialize<D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Self, D::Error> {
+        deserialize_code(deserializer)
+    }
+}
+
 impl Display for Sort {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
@@ -98,6 +153,27 @@ impl Into<u8> for Filter {
     }
 }
 
+/// Serialized as nyaa's `0`, `1` or `2`
+impl Serialize for Filter {
+    fn serialize<S: Serializer>(
+        &self,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.serialize_u8((*self).into())
+    }
+}
+
+impl<'de> Deserialize<'de> for Filter {
+    fn deserialize<D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Self, D::Error> {
+        match u8::deserialize(deserializer)? {
+            i @ 0..=2 => Ok(Filter::from(i)),
+            i => Err(serde::de::Error::custom(format!("Invalid filter {}", i))),
+        }
+    }
+}
+
 impl Display for Filter {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
@@ -108,7 +184,13 @@ impl Display for Filter {
     }
 }
 
-#[derive(Debug, PartialEq, Clone, Hash)]
+/// A search, serialized with nyaa's codes for its sort, order, filter and
+/// category. Missing fields take their default.
+#[derive(Debug, PartialEq, Clone, Hash, Serialize, Deserialize)]
+#[serde(
+    default,
+    bound(serialize = "C: Serialize", deserialize = "C: Category")
+)]
 pub struct Query<C> {
     search: String,
     page: u32,
@@ -118,6 +200,61 @@ pub struct Query<C> {
     category: C,
 }
 
+impl<C> Query<C> {
+    pub fn search(&self) -> &str {
+        &self.search
+    }
+
+    pub fn page(&self) -> u32 {
+        self.page
+    }
+
+    pub fn sort(&self) -> Sort {
+        self.sort
+    }
+
+    pub fn sort_order(&self) -> SortOrder {
+        self.sort_order
+    }
+
+    pub fn filter(&self) -> Filter {
+        self.filter
+    }
+
+    pub fn category(&self) -> &C {
+        &self.category
+    }
+
+    /// The same query in another category type
+    pub(crate) fn try_map_category<D, E>(
+        &self,
+        f: impl FnOnce(&C) -> Result<D, E>,
+    ) -> Result<Query<D>, E> {
+        Ok(Query {
+            search: self.search.clone(),
+            page: self.page,
+            sort: self.sort,
+            sort_order: self.sort_order,
+            filter: self.filter,
+            category: f(&self.category)?,
+        })
+    }
+}
+
+impl<C: Clone> Query<C> {
+    /// A builder starting from this query
+    pub fn to_builder(&self) -> QueryBuilder<C> {
+        QueryBuilder {
+            search: self.search.clone(),
+            page: self.page,
+            sort: self.sort,
+            sort_order: self.sort_order,
+            filter: self.filter,
+            category: self.category.clone(),
+        }
+    }
+}
+
 impl<C> Display for Query<C>
 where
     C: Category,
@@ -236,4 +373,61 @@ mod tests {
             .build();
         assert_eq!(query.to_string(), format!("q={}&p={}&s={}&o={}&f={}&c={}", "frieren", 1, "id", "desc", 0, "1_0"))
     }
+
+    #[test]
+    fn serde_uses_codes() {
+        let query = QueryBuilder::new()
+            .search("frieren")
+            .page(2)
+            .sort(Sort::Date)
+            .sort_order(SortOrder::Asecending)
+            .filter(Filter::TrustedOnly)
+            .category(NyaaCategory::AnimeEnglishTranslated)
+            .build();
+        let json = serde_json::to_string(&query).unwrap();
+        assert_eq!(
+            json,
+            r#"{"search":"frieren","page":2,"sort":"id","sort_order":"asc","filter":2,"category":"1_2"}"#
+        );
+        assert_eq!(serde_json::from_str::<Query<_>>(&json).unwrap(), query);
+
+        let saved: Query<NyaaCategory> =
+            serde_json::from_str(r#"{"search":"frieren","sort":"date"}"#)
+                .unwrap();
+        assert_eq!(saved.page(), 1);
+        assert_eq!(saved.sort(), Sort::Date);
+        assert_eq!(saved.category(), &NyaaCategory::All);
+        assert!(serde_json::from_str::<Filter>("3").is_err());
+        assert!(serde_json::from_str::<Sort>(r#""name""#).is_err());
+    }
+
+    #[test]
+    fn tweak_saved_query() {
+        let saved = QueryBuilder::new()
+            .search("frieren")
+            .category(NyaaCategory::Anime)
+            .build();
+        let next = saved.to_builder().page(2).build();
+        assert_eq!(next.search(), "frieren");
+        assert_eq!(next.page(), 2);
+        assert_eq!(next.category(), saved.category());
+    }
+
+    #[cfg(feature = "toml")]
+    #[test]
+    fn saved_query_from_toml() {
+        let query: Query<NyaaCategory> = toml::from_str(
+            r#"
+            search = "frieren"
+            sort = "size"
+            sort_order = "asc"
+            filter = 1
+            category = "Anime - Raw"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(query.sort(), Sort::Size);
+        assert_eq!(query.filter(), Filter::NoRemakes);
+        assert_eq!(query.category(), &NyaaCategory::AnimeRaw);
+    }
 }
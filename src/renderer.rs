@@ -0,0 +1,226 @@
+use crate::model::{Size, Torrent, TorrentId};
+
+/// Render torrents as a nyaa search result page.
+///
+/// This is the inverse of [`extract`](crate::extractor::extract): links
+/// starting with `base_url` are written relative to it, so
+/// `extract(&render(&torrents, base_url), base_url)` gives the torrents back.
+/// Dates are rendered to the second, like nyaa does.
+pub fn render(torrents: &[Torrent], base_url: &str) -> String {
+    let rows: String = torrents
+        .iter()
+        .map(|torrent| {
+            let link =
+                torrent.link.strip_prefix(base_url).unwrap_or(&torrent.link);
+            render_row(torrent, torrent.id(), link, &RowMeta::default())
+        })
+        .collect();
+    render_page("Browse", &render_table(&rows))
+}
+
+/// Listing details a [`Torrent`] doesn't carry
+#[derive(Debug, Clone, Copy)]
+pub struct RowMeta<'a> {
+    /// `success` for trusted, `danger` for remakes, `default` otherwise
+    pub class: &'a str,
+    pub comments: u32,
+}
+
+impl Default for RowMeta<'_> {
+    fn default() -> Self {
+        Self {
+            class: "default",
+            comments: 0,
+        }
+    }
+}
+
+/// A complete html document around `body`
+pub fn render_page(title: &str, body: &str) -> String {
+    format!(
+        "<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n\
+         <meta charset=\"utf-8\">\n<title>{}</title>\n\
+         </head>\n<body>\n<div class=\"container\">\n{}</div>\n\
+         </body>\n</html>\n",
+        escape(title),
+        body
+    )
+}
+
+/// The listing table around already rendered rows
+pub fn render_table(rows: &str) -> String {
+    format!(
+        "<div class=\"table-responsive\">\n<table class=\"table \
+         table-bordered table-hover table-striped torrent-list\">\n\
+         <thead>\n<tr>\n\
+         <th class=\"hdr-category text-center\">Category</th>\n\
+         <th class=\"hdr-name\">Name</th>\n\
+         <th class=\"hdr-comments text-center\">Comments</th>\n\
+         <th class=\"hdr-link text-center\">Link</th>\n\
+         <th class=\"hdr-size text-center\">Size</th>\n\
+         <th class=\"hdr-date text-center\">Date</th>\n\
+         <th class=\"hdr-seeders text-center\">Seeders</th>\n\
+         <th class=\"hdr-leechers text-center\">Leechers</th>\n\
+         <th class=\"hdr-downloads text-center\">Downloads</th>\n\
+         </tr>\n</thead>\n<tbody>\n{}</tbody>\n</table>\n</div>\n",
+        rows
+    )
+}
+
+/// One `<tr>` of the listing, with `link` as the download href
+pub fn render_row(
+    torrent: &Torrent,
+    id: Option<TorrentId>,
+    link: &str,
+    meta: &RowMeta<'_>,
+) -> String {
+    let view = match id {
+        Some(id) => format!("/view/{}", id),
+        None => link.to_string(),
+    };
+    let category = match &torrent.category {
+        Some(code) => format!(
+            "<a href=\"/?c={code}\" title=\"{code}\">\n\
+             <img src=\"/static/img/icons/nyaa/{code}.png\" \
+             alt=\"{code}\" class=\"category-icon\">\n</a>\n",
+            code = escape(code),
+        ),
+        None => String::new(),
+    };
+    let comments = match meta.comments {
+        0 => String::new(),
+        n => format!(
+            "<a href=\"{view}#comments\" class=\"comments\" \
+             title=\"{n} comments\">\n\
+             <i class=\"fa fa-comments-o\"></i>{n}</a>\n",
+            view = escape(&view),
+        ),
+    };
+    format!(
+        "<tr class=\"{class}\">\n\
+         <td>\n{category}</td>\n\
+         <td colspan=\"2\">\n{comments}\
+         <a href=\"{view}\" title=\"{title}\">{title}</a>\n</td>\n\
+         <td class=\"text-center\">\n\
+         <a href=\"{link}\"><i class=\"fa fa-fw fa-download\"></i></a>\n\
+         <a href=\"{magnet}\"><i class=\"fa fa-fw fa-magnet\"></i></a>\n\
+         </td>\n\
+         <td class=\"text-center\">{size}</td>\n\
+         <td class=\"text-center\" data-timestamp=\"{timestamp}\">{date}</td>\n\
+         <td class=\"text-center\">{seeders}</td>\n\
+         <td class=\"text-center\">{leechers}</td>\n\
+         <td class=\"text-center\">{downloads}</td>\n\
+         </tr>\n",
+        class = escape(meta.class),
+        category = category,
+        comments = comments,
+        view = escape(&view),
+        title = escape(&torrent.title),
+        link = escape(link),
+        magnet = escape(&torrent.magnet_url),
+        size = render_size(torrent.size),
+        timestamp = torrent.date.timestamp(),
+        date = torrent.date.format("%Y-%m-%d %H:%M"),
+        seeders = torrent.seeders,
+        leechers = torrent.leechers,
+        downloads = torrent.downloads,
+    )
+}
+
+/// Like `Size`'s `Display`, but without rounding to one decimal.
+fn render_size(size: Size) -> String {
+    match size {
+        Size::KB(value) => format!("{} KiB", value),
+        Size::MB(value) => format!("{} MiB", value),
+        Size::GB(value) => format!("{} GiB", value),
+        Size::TB(value) => format!("{} TiB", value),
+    }
+}
+
+/// Escape text for html content and attributes
+pub fn escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&#39;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::extractor::extract;
+    use crate::mock;
+    use proptest::prelude::*;
+
+    const BASE_URL: &str = "https://nyaa.si";
+
+    /// `Torrent`'s `PartialEq` only compares links.
+    fn fields(torrents: &[Torrent]) -> String {
+        format!("{:?}", torrents)
+    }
+
+    fn size() -> impl Strategy<Value = Size> {
+        (0u8..4, 0f32..1024.0).prop_map(|(unit, value)| match unit {
+            0 => Size::KB(value),
+            1 => Size::MB(value),
+            2 => Size::GB(value),
+            _ => Size::TB(value),
+        })
+    }
+
+    prop_compose! {
+        fn torrent()(
+            id in 1u32..10_000_000,
+            title in "[^\\s\\x00-\\x1f\\x7f][^\\x00-\\x1f\\x7f]{0,80}",
+            hash in "[0-9a-f]{40}",
+            name in "[ -~]{0,20}",
+            timestamp in 0i64..4_000_000_000,
+            seeders: u32,
+            leechers: u32,
+            downloads: u32,
+            size in size(),
+            category in prop::option::of((0u8..10, 0u8..10)),
+        ) -> Torrent {
+            Torrent {
+                title: title.trim_end().to_string(),
+                link: format!("{}/download/{}.torrent", BASE_URL, id),
+                magnet_url: format!("magnet:?xt=urn:btih:{}&dn={}", hash, name),
+                date: chrono::DateTime::from_timestamp(timestamp, 0).unwrap(),
+                seeders,
+                leechers,
+                downloads,
+                size,
+                category: category
+                    .map(|(main, sub)| format!("{}_{}", main, sub)),
+                uploader: None,
+            }
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn render_then_extract(
+            torrents in prop::collection::vec(torrent(), 0..10),
+        ) {
+            let html = render(&torrents, BASE_URL);
+            let extracted = extract(&html, BASE_URL).unwrap();
+            prop_assert_eq!(fields(&extracted), fields(&torrents));
+        }
+    }
+
+    #[test]
+    fn escape_markup() {
+        let torrent = mock::torrent(1)
+            .title("<b>A & B</b> \"quoted\"")
+            .magnet_url("magnet:?xt=urn:btih:00&dn=a&tr=b")
+            .size(Size::MB(1.25))
+            .build();
+        let torrents = [torrent];
+        let html = render(&torrents, BASE_URL);
+        assert!(html.contains("&lt;b&gt;A &amp; B&lt;/b&gt;"));
+        assert!(html.contains("href=\"/download/1.torrent\""));
+        let extracted = extract(&html, BASE_URL).unwrap();
+        assert_eq!(fields(&extracted), fields(&torrents));
+    }
+}
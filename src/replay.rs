@@ -0,0 +1,269 @@
+//! Record/replay transport for deterministic tests.
+//!
+//! Responses are stored as one JSON file per request in a fixtures
+//! directory, keyed by method and normalized url. Request bodies are not part
+//! of the key, since CSRF tokens and multipart boundaries change every run.
+
+use std::path::{Path, PathBuf};
+
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+use tracing::{event, Level};
+
+use crate::{
+    error::{Error, Result},
+    transport::{Request, ReqwestTransport, Response, Transport},
+};
+
+/// Environment variable read by [`ReplayMode::from_env`]
+pub const REPLAY_MODE_VAR: &str = "NYAA_SI_REPLAY";
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash, Default)]
+pub enum ReplayMode {
+    /// Replay saved responses, recording the missing ones
+    #[default]
+    Auto,
+    /// Always send requests and overwrite saved responses
+    Record,
+    /// Only replay, failing on requests without a saved response
+    Replay,
+}
+
+impl ReplayMode {
+    /// Read the mode from `NYAA_SI_REPLAY` (`auto`, `record` or `replay`),
+    /// defaulting to [`ReplayMode::Auto`]
+    pub fn from_env() -> Self {
+        match std::env::var(REPLAY_MODE_VAR).as_deref() {
+            Ok("record") => ReplayMode::Record,
+            Ok("replay") => ReplayMode::Replay,
+            _ => ReplayMode::Auto,
+        }
+    }
+}
+
+/// A transport that saves responses of `inner` and replays them afterwards
+#[derive(Debug, Clone)]
+pub struct ReplayTransport<T = ReqwestTransport> {
+    inner: T,
+    dir: PathBuf,
+    mode: ReplayMode,
+}
+
+impl ReplayTransport {
+    /// Replay from `dir`, recording missing responses from the live site
+    pub fn new<P: Into<PathBuf>>(dir: P) -> Self {
+        Self::with_inner(dir, ReqwestTransport::new())
+    }
+}
+
+impl<T: Transport> ReplayTransport<T> {
+    pub fn with_inner<P: Into<PathBuf>>(dir: P, inner: T) -> Self {
+        Self {
+            inner,
+            dir: dir.into(),
+            mode: ReplayMode::from_env(),
+        }
+    }
+
+    pub fn mode(mut self, mode: ReplayMode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+}
+
+impl<T: Transport> Transport for ReplayTransport<T> {
+    async fn send(&self, request: Request) -> Result<Response> {
+        let key = request_key(&request);
+        let path = self.dir.join(file_name(&key));
+
+        if self.mode != ReplayMode::Record && path.exists() {
+            event!(Level::DEBUG, "replaying {}", key);
+            return Fixture::load(&path)?.into_response();
+        }
+        if self.mode == ReplayMode::Replay {
+            return Err(Error::ReplayError(format!("No fixture for {}", key)));
+        }
+
+        event!(Level::DEBUG, "recording {}", key);
+        let res = self.inner.send(request).await?;
+        std::fs::create_dir_all(&self.dir)?;
+        Fixture::new(key, &res).save(&path)?;
+        Ok(res)
+    }
+}
+
+/// A saved response
+#[derive(Debug, Serialize, Deserialize)]
+struct Fixture {
+    request: String,
+    status: u16,
+    headers: Vec<(String, String)>,
+    #[serde(default)]
+    body: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    body_base64: Option<String>,
+}
+
+impl Fixture {
+    fn new(request: String, res: &Response) -> Self {
+        let headers = res
+            .headers()
+            .iter()
+            .filter_map(|(name, value)| {
+                Some((name.to_string(), value.to_str().ok()?.to_string()))
+            })
+            .collect();
+        let (body, body_base64) = match std::str::from_utf8(res.body()) {
+            Ok(body) => (body.to_string(), None),
+            Err(_) => (
+                String::new(),
+                Some(
+                    base64::engine::general_purpose::STANDARD
+                        .encode(res.body()),
+                ),
+            ),
+        };
+        Self {
+            request,
+            status: res.status().as_u16(),
+            headers,
+            body,
+            body_base64,
+        }
+    }
+
+    fn load(path: &Path) -> Result<Self> {
+        Ok(serde_json::from_slice(&std::fs::read(path)?)?)
+    }
+
+    fn save(&self, path: &Path) -> Result<()> {
+        std::fs::write(path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+
+    fn into_response(self) -> Result<Response> {
+        let body = match self.body_base64 {
+            Some(encoded) => base64::engine::general_purpose::STANDARD
+                .decode(encoded)
+                .map_err(|err| Error::ReplayError(err.to_string()))?,
+            None => self.body.into_bytes(),
+        };
+        let mut builder = http::Response::builder().status(self.status);
+        for (name, value) in &self.headers {
+            builder = builder.header(name, value);
+        }
+        Ok(builder.body(body)?)
+    }
+}
+
+/// `METHOD host/path?query` with the query pairs sorted and the port dropped,
+/// so that local test servers on random ports share fixtures.
+pub(crate) fn request_key(request: &Request) -> String {
+    let uri = request.uri();
+    let mut pairs: Vec<&str> = uri
+        .query()
+        .unwrap_or_default()
+        .split('&')
+        .filter(|pair| !pair.is_empty())
+        .collect();
+    pairs.sort_unstable();
+
+    let mut key = format!(
+        "{} {}{}",
+        request.method(),
+        uri.host().unwrap_or_default().to_ascii_lowercase(),
+        uri.path()
+    );
+    if !pairs.is_empty() {
+        key.push('?');
+        key.push_str(&pairs.join("&"));
+    }
+    key
+}
+
+/// A readable, filesystem-safe name for a request key.
+fn file_name(key: &str) -> String {
+    let readable: String = key
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .take(60)
+        .collect();
+    format!("{}-{:016x}.json", readable, fnv1a(key.as_bytes()))
+}
+
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ *byte as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transport::{request, RequestBuilderExt};
+    use http::Method;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[derive(Default)]
+    struct CountingTransport(AtomicUsize);
+
+    impl Transport for CountingTransport {
+        async fn send(&self, _request: Request) -> Result<Response> {
+            let count = self.0.fetch_add(1, Ordering::SeqCst);
+            Ok(http::Response::builder()
+                .status(200)
+                .header("x-count", count.to_string())
+                .body(vec![0xff, count as u8])?)
+        }
+    }
+
+    fn get(url: &str) -> Request {
+        request(Method::GET, url).empty().unwrap()
+    }
+
+    #[test]
+    fn normalize_request_key() {
+        assert_eq!(
+            request_key(&get("http://127.0.0.1:4321/?q=a%20b&p=1&c=0_0#x")),
+            "GET 127.0.0.1/?c=0_0&p=1&q=a%20b"
+        );
+        assert_eq!(
+            request_key(&get("https://NYAA.si/view/1")),
+            "GET nyaa.si/view/1"
+        );
+    }
+
+    #[tokio::test]
+    async fn record_then_replay() {
+        let dir = std::env::temp_dir()
+            .join(format!("nyaa-si-replay-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let inner = CountingTransport::default();
+
+        let recorder =
+            ReplayTransport::with_inner(&dir, &inner).mode(ReplayMode::Auto);
+        let first = recorder
+            .send(get("https://nyaa.si/?b=2&a=1"))
+            .await
+            .unwrap();
+        let again = recorder
+            .send(get("https://nyaa.si/?a=1&b=2"))
+            .await
+            .unwrap();
+        assert_eq!(inner.0.load(Ordering::SeqCst), 1);
+        assert_eq!(first.body(), again.body());
+        assert_eq!(again.headers()["x-count"], "0");
+
+        let replayer =
+            ReplayTransport::with_inner(&dir, &inner).mode(ReplayMode::Replay);
+        assert!(replayer.send(get("https://nyaa.si/?a=1&b=2")).await.is_ok());
+        assert!(replayer.send(get("https://nyaa.si/?a=2")).await.is_err());
+        assert_eq!(inner.0.load(Ordering::SeqCst), 1);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
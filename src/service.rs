@@ -0,0 +1,148 @@
+//! A [`tower::Service`] over any client, so searches can be wrapped in
+//! existing layers for timeouts, retries, rate limits or metrics.
+//!
+//! ```no_run
+//! use std::time::Duration;
+//!
+//! use nyaa_si::{service::ClientService, NyaaClient, QueryBuilder};
+//! use tower::{ServiceBuilder, ServiceExt};
+//!
+//! # async fn run() -> Result<(), tower::BoxError> {
+//! let service = ServiceBuilder::new()
+//!     .concurrency_limit(4)
+//!     .timeout(Duration::from_secs(10))
+//!     .service(ClientService::new(NyaaClient::new()));
+//! let query = QueryBuilder::new().search("frieren").build();
+//! let torrents = service.oneshot(query).await?;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::marker::PhantomData;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+
+use crate::{
+    category::Category,
+    client::{BoxFuture, DynClient},
+    error::{Error, Result},
+    model::Torrent,
+    query::Query,
+};
+
+/// A [`DynClient`] as a `Service<Query<C>>`, always ready
+///
+/// Clones share the client, so layers like `Buffer` or `Retry` can clone the
+/// service freely.
+pub struct ClientService<C, S: ?Sized> {
+    client: Arc<S>,
+    category: PhantomData<fn(C)>,
+}
+
+impl<C: Category, S: DynClient<C>> ClientService<C, S> {
+    pub fn new(client: S) -> Self {
+        Self::from(Arc::new(client))
+    }
+}
+
+impl<C: Category, S: DynClient<C> + ?Sized> ClientService<C, S> {
+    pub fn client(&self) -> &S {
+        &self.client
+    }
+}
+
+impl<C, S: ?Sized> From<Arc<S>> for ClientService<C, S> {
+    fn from(client: Arc<S>) -> Self {
+        Self {
+            client,
+            category: PhantomData,
+        }
+    }
+}
+
+impl<C, S: ?Sized> Clone for ClientService<C, S> {
+    fn clone(&self) -> Self {
+        Self::from(self.client.clone())
+    }
+}
+
+impl<C, S: ?Sized> std::fmt::Debug for ClientService<C, S> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ClientService").finish_non_exhaustive()
+    }
+}
+
+impl<C, S> tower::Service<Query<C>> for ClientService<C, S>
+where
+    C: Category,
+    S: DynClient<C> + ?Sized + 'static,
+{
+    type Response = Vec<Torrent>;
+    type Error = Error;
+    type Future = BoxFuture<'static, Result<Vec<Torrent>>>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, query: Query<C>) -> Self::Future {
+        let client = self.client.clone();
+        Box::pin(async move { client.get(&query).await })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use tower::{ServiceBuilder, ServiceExt};
+
+    use super::*;
+    use crate::any::{AnyCategory, AnySite};
+    use crate::mock::torrent;
+    use crate::testing::{FakeNyaa, FakeTorrent};
+    use crate::{NyaaCategory, NyaaClient, QueryBuilder};
+
+    fn fake_nyaa() -> FakeNyaa {
+        FakeNyaa::new().torrent(FakeTorrent::new(torrent(1).build()))
+    }
+
+    #[tokio::test]
+    async fn layered_client() {
+        let server = fake_nyaa().start().unwrap();
+        let service = ServiceBuilder::new()
+            .concurrency_limit(2)
+            .timeout(Duration::from_secs(10))
+            .service(ClientService::new(NyaaClient::with_transport(
+                server.transport(),
+            )));
+        let query = QueryBuilder::<NyaaCategory>::new().search("show").build();
+
+        let torrents = service.clone().oneshot(query.clone()).await.unwrap();
+        assert_eq!(torrents[0].title, "[Group] Show - 01");
+        let searches: Vec<_> = (0..3)
+            .map(|_| tokio::spawn(service.clone().oneshot(query.clone())))
+            .collect();
+        for search in searches {
+            assert_eq!(search.await.unwrap().unwrap().len(), 1);
+        }
+    }
+
+    #[tokio::test]
+    async fn dyn_client_errors() {
+        let server = fake_nyaa().start().unwrap();
+        let site: Arc<dyn DynClient> =
+            Arc::new(AnySite::with_transport("sukebei", server.transport()));
+        let service = ClientService::from(site);
+        assert_eq!(service.client().base_url(), "https://sukebei.nyaa.si");
+
+        let query = QueryBuilder::new()
+            .category(AnyCategory::Nyaa(NyaaCategory::Anime))
+            .build();
+        let err = service.oneshot(query).await.unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Category error: sukebei has no category nyaa:1_0"
+        );
+    }
+}
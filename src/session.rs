@@ -0,0 +1,392 @@
+use std::collections::BTreeMap;
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::marker::PhantomData;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+use http::header::{COOKIE, SET_COOKIE};
+use http::{request::Builder, Method, StatusCode};
+use tracing::{event, Level};
+
+use crate::{
+    category::Category,
+    error::{Error, Result},
+    extractor::{extract_owned, Extractor},
+    form::{error_message, HtmlForm},
+    model::Torrent,
+    query::Query,
+    transport::{
+        self, Request, RequestBuilderExt, ReqwestTransport, Response,
+        ResponseExt, Transport,
+    },
+};
+
+/// Cookies of a single site, persisted as `name=value` lines.
+#[derive(Debug, Default)]
+pub struct CookieJar {
+    cookies: Mutex<BTreeMap<String, String>>,
+}
+
+impl CookieJar {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Read a jar previously written by [`CookieJar::save`].
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let content = std::fs::read_to_string(path)?;
+        let jar = Self::new();
+        for line in content.lines() {
+            if let Some((name, value)) = line.trim().split_once('=') {
+                jar.insert(name, value);
+            }
+        }
+        Ok(jar)
+    }
+
+    /// Write the cookies to `path`, readable only by the owner on unix as
+    /// they hold the login token.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let content: String = self
+            .cookies
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(name, value)| format!("{}={}\n", name, value))
+            .collect();
+        let mut options = OpenOptions::new();
+        options.write(true).create(true).truncate(true);
+        #[cfg(unix)]
+        std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+        let mut file = options.open(path)?;
+        // the mode only applies to new files
+        #[cfg(unix)]
+        file.set_permissions(std::os::unix::fs::PermissionsExt::from_mode(
+            0o600,
+        ))?;
+        file.write_all(content.as_bytes())?;
+        Ok(())
+    }
+
+    pub fn get(&self, name: &str) -> Option<String> {
+        self.cookies.lock().unwrap().get(name).cloned()
+    }
+
+    pub fn insert<N: Into<String>, V: Into<String>>(&self, name: N, value: V) {
+        self.cookies
+            .lock()
+            .unwrap()
+            .insert(name.into(), value.into());
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.cookies.lock().unwrap().is_empty()
+    }
+
+    /// Apply a `Set-Cookie` header, removing expired cookies.
+    fn store(&self, set_cookie: &str) {
+        let mut attributes = set_cookie.split(';');
+        let Some((name, value)) =
+            attributes.next().and_then(|pair| pair.split_once('='))
+        else {
+            return;
+        };
+        let expired = attributes.any(|attr| {
+            let attr = attr.trim().to_ascii_lowercase();
+            attr == "max-age=0" || attr.starts_with("expires=thu, 01 jan 1970")
+        });
+
+        let mut cookies = self.cookies.lock().unwrap();
+        if expired || value.is_empty() {
+            cookies.remove(name.trim());
+        } else {
+            cookies.insert(name.trim().to_string(), value.trim().to_string());
+        }
+    }
+
+    fn header(&self) -> Option<String> {
+        let cookies = self.cookies.lock().unwrap();
+        if cookies.is_empty() {
+            return None;
+        }
+        let pairs: Vec<String> = cookies
+            .iter()
+            .map(|(name, value)| format!("{}={}", name, value))
+            .collect();
+        Some(pairs.join("; "))
+    }
+}
+
+/// A cookie-backed session on a nyaa site.
+///
+/// The category parameter ties the session to one site, so that uploads and
+/// edits can only use that site's categories.
+#[derive(Debug)]
+pub struct Session<C, T = ReqwestTransport> {
+    transport: T,
+    base_url: String,
+    cookies: CookieJar,
+    extractor: Arc<Extractor>,
+    _category: PhantomData<C>,
+}
+
+impl<C: Category> Session<C> {
+    /// Create an anonymous session on the site at `base_url`.
+    pub fn new<S: Into<String>>(base_url: S) -> Self {
+        Self::with_cookies(base_url, CookieJar::new())
+    }
+
+    /// Resume a session from previously saved cookies.
+    pub fn with_cookies<S: Into<String>>(
+        base_url: S,
+        cookies: CookieJar,
+    ) -> Self {
+        Self::with_transport(ReqwestTransport::new(), base_url, cookies)
+    }
+}
+
+impl<C: Category, T: Transport> Session<C, T> {
+    /// Create a session that sends its requests through `transport`.
+    pub fn with_transport<S: Into<String>>(
+        transport: T,
+        base_url: S,
+        cookies: CookieJar,
+    ) -> Self {
+        Self {
+            transport,
+            base_url: base_url.into(),
+            cookies,
+            extractor: Arc::default(),
+            _category: PhantomData,
+        }
+    }
+
+    /// Parse search results with a custom extractor
+    pub fn with_extractor<E: Into<Arc<Extractor>>>(
+        mut self,
+        extractor: E,
+    ) -> Self {
+        self.extractor = extractor.into();
+        self
+    }
+
+    pub fn base_url(&self) -> &str {
+        &self.base_url
+    }
+
+    pub fn cookies(&self) -> &CookieJar {
+        &self.cookies
+    }
+
+    /// Log in with the site's login form.
+    #[tracing::instrument(skip(self, password))]
+    pub async fn login(&self, username: &str, password: &str) -> Result<()> {
+        let page = self.fetch("/login").await?;
+        let form = HtmlForm::find(&page, "password")?;
+
+        let res = self
+            .send(self.post("/login").form(&[
+                ("csrf_token", form.csrf_token()?),
+                ("username", username),
+                ("password", password),
+            ])?)
+            .await?;
+
+        if res.status().is_redirection() {
+            event!(Level::DEBUG, "logged in as {}", username);
+            return Ok(());
+        }
+        let page = res.text();
+        Err(Error::LoginError(
+            error_message(&page).unwrap_or_else(|| "Login failed".into()),
+        ))
+    }
+
+    /// Check whether the cookies still belong to a logged-in user.
+    pub async fn is_logged_in(&self) -> Result<bool> {
+        let page = self.fetch("/").await?;
+        Ok(page.contains("href=\"/logout\""))
+    }
+
+    /// Search as the session's user, which includes their own hidden and
+    /// pending torrents.
+    #[tracing::instrument(skip(self, query))]
+    pub async fn get(&self, query: &Query<C>) -> Result<Vec<Torrent>> {
+        let page = self.fetch(&format!("/?{}", query)).await?;
+        extract_owned(self.extractor.clone(), page, self.base_url.clone()).await
+    }
+
+    pub(crate) fn request(&self, method: Method, path: &str) -> Builder {
+        let url = format!("{}{}", self.base_url, path);
+        event!(Level::DEBUG, "url = {}", url);
+
+        let builder = transport::request(method, &url);
+        match self.cookies.header() {
+            Some(cookies) => builder.header(COOKIE, cookies),
+            None => builder,
+        }
+    }
+
+    pub(crate) fn post(&self, path: &str) -> Builder {
+        self.request(Method::POST, path)
+    }
+
+    /// Send a request, keeping any cookies the site sets.
+    pub(crate) async fn send_raw(&self, request: Request) -> Result<Response> {
+        let res = self.transport.send(request).await?;
+        for set_cookie in res.headers().get_all(SET_COOKIE) {
+            if let Ok(set_cookie) = set_cookie.to_str() {
+                self.cookies.store(set_cookie);
+            }
+        }
+        Ok(res)
+    }
+
+    /// Like [`Session::send_raw`], but fails on error statuses.
+    pub(crate) async fn send(&self, request: Request) -> Result<Response> {
+        let res = self.send_raw(request).await?;
+        if res.status().is_client_error() || res.status().is_server_error() {
+            return Err(Error::StatusError(res.status().as_u16()));
+        }
+        Ok(res)
+    }
+
+    /// Fetch a page, failing on anything but `200 OK`.
+    pub(crate) async fn fetch(&self, path: &str) -> Result<String> {
+        let res = self.send(self.request(Method::GET, path).empty()?).await?;
+        if res.status() != StatusCode::OK {
+            return Err(Error::StatusError(res.status().as_u16()));
+        }
+        Ok(res.text())
+    }
+}
+
+/// Turn the statuses nyaa answers forbidden actions with into typed errors.
+pub(crate) fn access_error(err: Error, what: &str) -> Error {
+    match err {
+        Error::StatusError(403) => {
+            Error::PermissionError(format!("Not allowed to change {}", what))
+        }
+        Error::StatusError(404) => {
+            Error::NotFoundError(format!("{} not found", what))
+        }
+        // anonymous users are sent to the login page
+        Error::StatusError(code) if (300..400).contains(&code) => {
+            Error::PermissionError(format!("Login required to change {}", what))
+        }
+        err => err,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::{MockResponse, MockServer};
+    use crate::NyaaCategory;
+
+    const LOGIN_PAGE: &str = r#"<nav class="navbar">
+    <form class="navbar-form navbar-right form" action="/" method="get">
+        <input type="text" class="form-control search-bar" name="q" value="">
+    </form>
+    </nav>
+    <form class="form-signin" method="POST">
+        <input id="csrf_token" name="csrf_token" type="hidden" value="t0k3n">
+        <input id="username" name="username" type="text" value="">
+        <input id="password" name="password" type="password" value="">
+        <input type="submit" value="Login">
+    </form>"#;
+
+    fn login_server() -> MockServer {
+        MockServer::start(|req| {
+            match (req.method.as_str(), req.path.as_str()) {
+                ("GET", "/login") => MockResponse::html(LOGIN_PAGE)
+                    .with_header(
+                        "Set-Cookie",
+                        "session=anon; HttpOnly; Path=/",
+                    ),
+                ("POST", "/login") => {
+                    let form = req.form();
+                    let valid = req.header("cookie") == Some("session=anon")
+                        && form["csrf_token"] == "t0k3n"
+                        && form["username"] == "user"
+                        && form["password"] == "hunter2";
+                    if valid {
+                        MockResponse::redirect("/")
+                            .with_header("Set-Cookie", "session=user; Path=/")
+                    } else {
+                        MockResponse::html(
+                            r#"<div class="alert alert-danger">
+                        <strong>Login failed!</strong> Incorrect username or password.
+                        </div>"#,
+                        )
+                    }
+                }
+                ("GET", "/")
+                    if req.header("cookie") == Some("session=user") =>
+                {
+                    MockResponse::html(r#"<a href="/logout">Logout</a>"#)
+                }
+                ("GET", "/") => {
+                    MockResponse::html(r#"<a href="/login">Login</a>"#)
+                }
+                _ => MockResponse::status(404),
+            }
+        }).unwrap()
+    }
+
+    #[tokio::test]
+    async fn login_keeps_session_cookie() {
+        let server = login_server();
+        let session = Session::<NyaaCategory>::new(server.url());
+
+        assert!(!session.is_logged_in().await.unwrap());
+        session.login("user", "hunter2").await.unwrap();
+        assert_eq!(session.cookies().get("session").unwrap(), "user");
+        assert!(session.is_logged_in().await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn login_reports_failure() {
+        let server = login_server();
+        let session = Session::<NyaaCategory>::new(server.url());
+
+        let err = session.login("user", "wrong").await.unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Login error: Login failed! Incorrect username or password."
+        );
+    }
+
+    #[tokio::test]
+    async fn restore_saved_cookies() {
+        let server = login_server();
+        let path = std::env::temp_dir()
+            .join(format!("nyaa-si-cookies-{}.txt", std::process::id()));
+        let session = Session::<NyaaCategory>::new(server.url());
+        session.login("user", "hunter2").await.unwrap();
+        assert!(!format!("{:?}", session).contains("hunter2"));
+        session.cookies().save(&path).unwrap();
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+
+        let cookies = CookieJar::load(&path).unwrap();
+        let resumed =
+            Session::<NyaaCategory>::with_cookies(server.url(), cookies);
+        assert!(resumed.is_logged_in().await.unwrap());
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn expired_cookie_is_removed() {
+        let jar = CookieJar::new();
+        jar.store("session=abc; Path=/");
+        assert_eq!(jar.header().unwrap(), "session=abc");
+        jar.store("session=; Expires=Thu, 01 Jan 1970 00:00:00 GMT; Path=/");
+        assert!(jar.is_empty());
+    }
+}
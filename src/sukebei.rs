@@ -1,14 +1,28 @@
 use std::fmt::Display;
-use tokio::task::spawn_blocking;
+use std::str::FromStr;
+use std::sync::Arc;
+
+use http::Method;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use tracing::{event, Level};
 
 use crate::{
-    category::Category, client::Client, error::Result, extractor::extract,
-    model::Torrent, query::Query,
+    canary::{self, HealthReport},
+    category::{self, Category},
+    client::Client,
+    error::{Error, Result},
+    extractor::{extract_owned, fetch_uploader, Extractor},
+    model::{Torrent, TorrentId},
+    query::Query,
+    session::{CookieJar, Session},
+    transport::{
+        self, RequestBuilderExt, ReqwestTransport, ResponseExt, Transport,
+    },
 };
 
-#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq, Default)]
 pub enum SukebeiCategory {
+    #[default]
     All,
     Art,
     ArtAnime,
@@ -21,36 +35,97 @@ pub enum SukebeiCategory {
     RealLifeVideos,
 }
 
+/// Code, name and description of each category, in declaration order
+const CATEGORIES: [(&str, &str, &str); 10] = [
+    ("0_0", "All categories", "Everything on the site"),
+    ("1_0", "Art", "Drawn adult content"),
+    ("1_1", "Art - Anime", "Adult anime"),
+    ("1_2", "Art - Doujinshi", "Adult self-published works"),
+    ("1_3", "Art - Games", "Adult games and visual novels"),
+    ("1_4", "Art - Manga", "Adult manga"),
+    ("1_5", "Art - Pictures", "Adult artwork and CG sets"),
+    ("2_0", "Real Life", "Live action adult content"),
+    (
+        "2_1",
+        "Real Life - Photobooks and Pictures",
+        "Adult photobooks and pictures",
+    ),
+    ("2_2", "Real Life - Videos", "Adult live action videos"),
+];
+
+impl SukebeiCategory {
+    /// Every category, starting with the default `All`
+    pub const ALL: &'static [Self] = &[
+        Self::All,
+        Self::Art,
+        Self::ArtAnime,
+        Self::ArtDoujinshi,
+        Self::ArtGames,
+        Self::ArtManga,
+        Self::ArtPictures,
+        Self::RealLife,
+        Self::RealLifePhotobooks,
+        Self::RealLifeVideos,
+    ];
+}
+
 impl Display for SukebeiCategory {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        match self {
-            Self::All => write!(f, "0_0"),
-            Self::Art => write!(f, "1_0"),
-            Self::ArtAnime => write!(f, "1_1"),
-            Self::ArtDoujinshi => write!(f, "1_2"),
-            Self::ArtGames => write!(f, "1_3"),
-            Self::ArtManga => write!(f, "1_4"),
-            Self::ArtPictures => write!(f, "1_5"),
-            Self::RealLife => write!(f, "2_0"),
-            Self::RealLifePhotobooks => write!(f, "2_1"),
-            Self::RealLifeVideos => write!(f, "2_2"),
-        }
+        f.write_str(self.code())
+    }
+}
+
+impl FromStr for SukebeiCategory {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        category::parse(s)
+    }
+}
+
+impl Serialize for SukebeiCategory {
+    fn serialize<S: Serializer>(
+        &self,
+        serializer: S,
+    ) -> std::result::Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.code())
     }
 }
 
-impl Default for SukebeiCategory {
-    fn default() -> Self {
-        Self::All
+impl<'de> Deserialize<'de> for SukebeiCategory {
+    fn deserialize<D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> std::result::Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        s.parse().map_err(serde::de::Error::custom)
     }
 }
 
-impl Category for SukebeiCategory {}
+impl Category for SukebeiCategory {
+    fn all() -> Vec<Self> {
+        Self::ALL.to_vec()
+    }
+
+    fn code(&self) -> &'static str {
+        CATEGORIES[*self as usize].0
+    }
+
+    fn name(&self) -> &'static str {
+        CATEGORIES[*self as usize].1
+    }
+
+    fn description(&self) -> &'static str {
+        CATEGORIES[*self as usize].2
+    }
+}
 
 pub type SukebeiQuery = Query<SukebeiCategory>;
+pub type SukebeiSession<T = ReqwestTransport> = Session<SukebeiCategory, T>;
 
 #[derive(Debug, Default)]
-pub struct SukebeiClient {
-    inner: reqwest::Client,
+pub struct SukebeiClient<T = ReqwestTransport> {
+    transport: T,
+    extractor: Arc<Extractor>,
 }
 
 impl SukebeiClient {
@@ -59,7 +134,70 @@ impl SukebeiClient {
     }
 }
 
-impl Client<SukebeiCategory> for SukebeiClient {
+impl<T: Transport> SukebeiClient<T> {
+    /// Send requests through a custom transport
+    pub fn with_transport(transport: T) -> Self {
+        Self {
+            transport,
+            extractor: Arc::default(),
+        }
+    }
+
+    /// Parse results with a custom extractor, e.g. to follow markup changes
+    pub fn with_extractor<E: Into<Arc<Extractor>>>(
+        mut self,
+        extractor: E,
+    ) -> Self {
+        self.extractor = extractor.into();
+        self
+    }
+
+    /// Fetch a search that should have results and check its page still has
+    /// the layout the extractor expects
+    pub async fn check_layout(
+        &self,
+        query: &Query<SukebeiCategory>,
+    ) -> Result<HealthReport> {
+        canary::check(&self.transport, &self.extractor, Self::BASE_URL, query)
+            .await
+    }
+
+    /// Fetch who uploaded torrent `id` from its view page, `None` for
+    /// anonymous uploads, e.g. to fill [`Torrent::uploader`] before filtering
+    pub async fn uploader(&self, id: TorrentId) -> Result<Option<String>> {
+        fetch_uploader(&self.transport, Self::BASE_URL, id).await
+    }
+}
+
+impl<T: Transport + Clone> SukebeiClient<T> {
+    /// Log in to sukebei.nyaa.si and return the authenticated session
+    pub async fn login(
+        &self,
+        username: &str,
+        password: &str,
+    ) -> Result<SukebeiSession<T>> {
+        let session = SukebeiSession::with_transport(
+            self.transport.clone(),
+            Self::BASE_URL,
+            CookieJar::new(),
+        )
+        .with_extractor(self.extractor.clone());
+        session.login(username, password).await?;
+        Ok(session)
+    }
+
+    /// Resume a session from previously saved cookies
+    pub fn resume(&self, cookies: CookieJar) -> SukebeiSession<T> {
+        SukebeiSession::with_transport(
+            self.transport.clone(),
+            Self::BASE_URL,
+            cookies,
+        )
+        .with_extractor(self.extractor.clone())
+    }
+}
+
+impl<T: Transport> Client<SukebeiCategory> for SukebeiClient<T> {
     const BASE_URL: &'static str = "https://sukebei.nyaa.si";
 
     #[tracing::instrument(skip(self))]
@@ -71,27 +209,42 @@ impl Client<SukebeiCategory> for SukebeiClient {
 
         event!(Level::DEBUG, "url = {}", url);
 
-        let res = self.inner.get(url).send().await?.text().await?;
+        let res = self
+            .transport
+            .send(transport::request(Method::GET, &url).empty()?)
+            .await?;
+        if !res.status().is_success() {
+            return Err(Error::StatusError(res.status().as_u16()));
+        }
 
-        spawn_blocking(move || extract(&res, Self::BASE_URL)).await?
+        extract_owned(
+            self.extractor.clone(),
+            res.text(),
+            Self::BASE_URL.to_string(),
+        )
+        .await
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::mock::fixtures;
     use crate::query::QueryBuilder;
     use crate::query::Sort;
 
     #[tokio::test]
     async fn test_get() {
-        let client = SukebeiClient::new();
+        let client = SukebeiClient::with_transport(fixtures());
         let query = QueryBuilder::new()
             .search("dl")
             .sort(Sort::Downloads)
             .build();
         let res = client.get(&query).await.unwrap();
 
-        println!("{:#?}", res.get(0).unwrap());
+        let first = res.first().unwrap();
+        assert!(first.title.to_lowercase().contains("dl版"));
+        assert!(first.link.starts_with(<SukebeiClient>::BASE_URL));
+        assert!(res.windows(2).all(|w| w[0].downloads >= w[1].downloads));
     }
 }
@@ -0,0 +1,244 @@
+//! Incremental fetching of new uploads, for indexers polling a listing.
+//!
+//! ```no_run
+//! use nyaa_si::{sync::SyncQuery, NyaaCategory, NyaaClient, QueryBuilder};
+//!
+//! # async fn run() -> nyaa_si::error::Result<()> {
+//! let client = NyaaClient::new();
+//! let query = QueryBuilder::new().category(NyaaCategory::Anime).build();
+//! let mut last_seen = None;
+//! let mut max_pages = 10;
+//! loop {
+//!     let batch = SyncQuery::new(query.clone())
+//!         .since(last_seen)
+//!         .max_pages(max_pages)
+//!         .get(&client)
+//!         .await?;
+//!     for torrent in &batch.torrents {
+//!         println!("{}", torrent.title);
+//!     }
+//!     // after the first sync the mark only moves once paging closes the
+//!     // gap, so page further next time until it does
+//!     max_pages = if batch.complete { 10 } else { max_pages * 2 };
+//!     last_seen = batch.high_water_mark;
+//! #   break;
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use std::collections::HashSet;
+
+use crate::{
+    category::Category,
+    client::DynClient,
+    error::{Error, Result},
+    model::{Torrent, TorrentId},
+    query::{Query, Sort, SortOrder},
+};
+
+/// Everything newer than a known torrent id, paged through newest first
+#[derive(Debug, Clone, PartialEq)]
+pub struct SyncQuery<C> {
+    query: Query<C>,
+    since: Option<TorrentId>,
+    max_pages: u32,
+}
+
+/// New torrents found by a [`SyncQuery`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct SyncBatch {
+    /// Oldest first
+    pub torrents: Vec<Torrent>,
+    /// The id to pass as `since` next time: the newest id seen, or `since`
+    /// itself when paging stopped short, as the torrents in between are
+    /// still missing. A first sync without `since` starts from the newest id
+    /// either way, leaving what is past the page limit out
+    pub high_water_mark: Option<TorrentId>,
+    /// Whether paging reached the `since` id or the end of the listing,
+    /// rather than stopping at the page limit with a gap left behind, in
+    /// which case the next sync returns these torrents again
+    pub complete: bool,
+}
+
+impl<C: Category> SyncQuery<C> {
+    /// Sync the results of `query`, whose page and sort are replaced
+    pub fn new(query: Query<C>) -> Self {
+        Self {
+            query,
+            since: None,
+            max_pages: 10,
+        }
+    }
+
+    /// Only return torrents newer than `id`, or every page up to the limit
+    /// for `None`, the first sync
+    pub fn since<I: Into<Option<TorrentId>>>(mut self, id: I) -> Self {
+        self.since = id.into();
+        self
+    }
+
+    /// Stop after `max_pages` pages, 10 by default
+    pub fn max_pages(mut self, max_pages: u32) -> Self {
+        self.max_pages = max_pages;
+        self
+    }
+
+    /// Page through the listing newest first until reaching an id at or
+    /// below `since`
+    ///
+    /// The `since` torrent itself may have been deleted, so any older id ends
+    /// paging, as does an empty page or a `404 Not Found` past the last
+    /// one. Torrents shifting to the next page as new ones are uploaded
+    /// are only returned once.
+    #[tracing::instrument(skip(self, client))]
+    pub async fn get<S>(&self, client: &S) -> Result<SyncBatch>
+    where
+        S: DynClient<C> + ?Sized,
+    {
+        let mut seen = HashSet::new();
+        let mut torrents = Vec::new();
+        let mut complete = false;
+
+        for page in 1..=self.max_pages {
+            let query = self
+                .query
+                .to_builder()
+                .sort(Sort::Date)
+                .sort_order(SortOrder::Descending)
+                .page(page)
+                .build();
+            let results = match client.get(&query).await {
+                Err(Error::StatusError(404)) if page > 1 => Vec::new(),
+                results => results?,
+            };
+            if results.is_empty() {
+                complete = true;
+                break;
+            }
+
+            let mut reached = false;
+            for torrent in results {
+                let Some(id) = torrent.id() else { continue };
+                if self.since.is_some_and(|since| id <= since) {
+                    reached = true;
+                } else if seen.insert(id) {
+                    torrents.push((id, torrent));
+                }
+            }
+            if reached {
+                complete = true;
+                break;
+            }
+        }
+
+        torrents.sort_by_key(|(id, _)| *id);
+        let high_water_mark = if complete || self.since.is_none() {
+            torrents.last().map(|(id, _)| *id).or(self.since)
+        } else {
+            self.since
+        };
+        Ok(SyncBatch {
+            torrents: torrents
+                .into_iter()
+                .map(|(_, torrent)| torrent)
+                .collect(),
+            high_water_mark,
+            complete,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::torrent;
+    use crate::testing::FakeNyaa;
+    use crate::{NyaaCategory, NyaaClient, QueryBuilder};
+
+    fn ids(batch: &SyncBatch) -> Vec<u32> {
+        batch
+            .torrents
+            .iter()
+            .map(|torrent| torrent.id().unwrap().0)
+            .collect()
+    }
+
+    async fn sync(
+        ids: &[u32],
+        since: Option<u32>,
+        max_pages: u32,
+    ) -> SyncBatch {
+        sync_with(FakeNyaa::new(), ids, since, max_pages).await
+    }
+
+    async fn sync_with(
+        site: FakeNyaa,
+        ids: &[u32],
+        since: Option<u32>,
+        max_pages: u32,
+    ) -> SyncBatch {
+        let server = site
+            .per_page(2)
+            .torrents(ids.iter().map(|id| torrent(*id).build()))
+            .start()
+            .unwrap();
+        let client = NyaaClient::with_transport(server.transport());
+        let query = QueryBuilder::<NyaaCategory>::new().build();
+        SyncQuery::new(query)
+            .since(since.map(TorrentId))
+            .max_pages(max_pages)
+            .get(&client)
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn fetch_new_torrents() {
+        let batch = sync(&[1, 2, 3, 4, 5, 6, 7], Some(3), 10).await;
+        assert_eq!(ids(&batch), [4, 5, 6, 7]);
+        assert_eq!(batch.high_water_mark, Some(TorrentId(7)));
+        assert!(batch.complete);
+
+        let batch = sync(&[1, 2, 3], Some(3), 10).await;
+        assert!(batch.torrents.is_empty());
+        assert_eq!(batch.high_water_mark, Some(TorrentId(3)));
+
+        let batch = sync(&[1, 2, 3], None, 10).await;
+        assert_eq!(ids(&batch), [1, 2, 3]);
+        assert!(batch.complete);
+    }
+
+    #[tokio::test]
+    async fn known_torrent_deleted() {
+        let batch = sync(&[1, 2, 4, 5, 6], Some(3), 10).await;
+        assert_eq!(ids(&batch), [4, 5, 6]);
+        assert_eq!(batch.high_water_mark, Some(TorrentId(6)));
+        assert!(batch.complete);
+    }
+
+    #[tokio::test]
+    async fn stop_at_page_limit() {
+        let batch = sync(&[1, 2, 3, 4, 5, 6, 7, 8], Some(1), 2).await;
+        assert_eq!(ids(&batch), [5, 6, 7, 8]);
+        assert_eq!(batch.high_water_mark, Some(TorrentId(1)));
+        assert!(!batch.complete);
+    }
+
+    #[tokio::test]
+    async fn first_sync_starts_at_newest() {
+        let batch = sync(&[1, 2, 3, 4, 5, 6, 7, 8], None, 2).await;
+        assert_eq!(ids(&batch), [5, 6, 7, 8]);
+        assert_eq!(batch.high_water_mark, Some(TorrentId(8)));
+        assert!(!batch.complete);
+    }
+
+    #[tokio::test]
+    async fn not_found_past_end() {
+        let site = FakeNyaa::new().not_found_past_end(true);
+        let batch = sync_with(site, &[1, 2, 3, 4], None, 10).await;
+        assert_eq!(ids(&batch), [1, 2, 3, 4]);
+        assert_eq!(batch.high_water_mark, Some(TorrentId(4)));
+        assert!(batch.complete);
+    }
+}
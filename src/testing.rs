@@ -0,0 +1,894 @@
+//! Local stand-ins of nyaa for tests, enabled by the `testing` feature.
+//!
+//! [`MockServer`] is a minimal HTTP/1.1 server that answers every request
+//! with a closure, and [`FakeNyaa`] builds on it to serve search listings,
+//! view pages, RSS feeds and `.torrent` downloads from in-memory torrents.
+//! Both stop serving and free their port when the server is dropped.
+
+use std::cmp::Ordering::Equal;
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use crate::{
+    category::Category,
+    error::Result,
+    filter::Filterable,
+    model::{Size, Torrent, TorrentId},
+    renderer::{escape, render_page, render_row, render_table, RowMeta},
+    transport::{Request, ReqwestTransport, Response, Transport},
+};
+
+/// A request received by a [`MockServer`]
+#[derive(Debug, Clone, Default)]
+pub struct MockRequest {
+    pub method: String,
+    pub path: String,
+    pub headers: HashMap<String, String>,
+    pub body: Vec<u8>,
+}
+
+impl MockRequest {
+    /// The path without the query string
+    pub fn route(&self) -> &str {
+        self.path.split(['?', '#']).next().unwrap_or_default()
+    }
+
+    /// Decode the query string.
+    pub fn query(&self) -> HashMap<String, String> {
+        match self.path.split_once('?') {
+            Some((_, query)) => decode_pairs(query),
+            None => HashMap::new(),
+        }
+    }
+
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .get(&name.to_ascii_lowercase())
+            .map(|s| s.as_str())
+    }
+
+    pub fn body_str(&self) -> &str {
+        std::str::from_utf8(&self.body).unwrap_or_default()
+    }
+
+    /// Decode an `application/x-www-form-urlencoded` body.
+    pub fn form(&self) -> HashMap<String, String> {
+        decode_pairs(self.body_str())
+    }
+
+    /// Split a `multipart/form-data` body into its named parts.
+    pub fn multipart(&self) -> HashMap<String, Vec<u8>> {
+        let boundary = self
+            .header("content-type")
+            .and_then(|ct| ct.split_once("boundary="))
+            .map(|(_, boundary)| format!("--{}", boundary))
+            .unwrap_or_default();
+        let mut parts = HashMap::new();
+
+        for part in split(&self.body, boundary.as_bytes()) {
+            let Some(head_end) = find(part, b"\r\n\r\n") else {
+                continue;
+            };
+            let head = String::from_utf8_lossy(&part[..head_end]);
+            let Some(name) = head
+                .split("name=\"")
+                .nth(1)
+                .and_then(|rest| rest.split('"').next())
+            else {
+                continue;
+            };
+            let content = &part[head_end + 4..];
+            let content = content.strip_suffix(b"\r\n").unwrap_or(content);
+            parts.insert(name.to_string(), content.to_vec());
+        }
+        parts
+    }
+}
+
+/// The answer of a [`MockServer`] handler
+#[derive(Debug, Clone)]
+pub struct MockResponse {
+    pub status: u16,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+impl MockResponse {
+    pub fn html<S: Into<String>>(body: S) -> Self {
+        Self {
+            status: 200,
+            headers: vec![("Content-Type".into(), "text/html".into())],
+            body: body.into().into_bytes(),
+        }
+    }
+
+    pub fn bytes<B: Into<Vec<u8>>>(content_type: &str, body: B) -> Self {
+        Self {
+            status: 200,
+            headers: vec![("Content-Type".into(), content_type.into())],
+            body: body.into(),
+        }
+    }
+
+    pub fn redirect(location: &str) -> Self {
+        Self {
+            status: 302,
+            headers: vec![("Location".into(), location.into())],
+            body: Vec::new(),
+        }
+    }
+
+    pub fn status(status: u16) -> Self {
+        Self {
+            status,
+            headers: Vec::new(),
+            body: Vec::new(),
+        }
+    }
+
+    pub fn with_status(mut self, status: u16) -> Self {
+        self.status = status;
+        self
+    }
+
+    pub fn with_header(mut self, name: &str, value: &str) -> Self {
+        self.headers.push((name.into(), value.into()));
+        self
+    }
+}
+
+type Handler = dyn Fn(&MockRequest) -> MockResponse + Send + Sync;
+
+/// Serves every connection with `handler` until dropped.
+#[derive(Debug)]
+pub struct MockServer {
+    url: String,
+    addr: SocketAddr,
+    stop: Arc<AtomicBool>,
+    accept: Option<JoinHandle<()>>,
+}
+
+impl MockServer {
+    /// Listen on a free local port
+    pub fn start<F>(handler: F) -> Result<Self>
+    where
+        F: Fn(&MockRequest) -> MockResponse + Send + Sync + 'static,
+    {
+        let listener = TcpListener::bind("127.0.0.1:0")?;
+        let addr = listener.local_addr()?;
+        let handler: Arc<Handler> = Arc::new(handler);
+        let stop = Arc::new(AtomicBool::new(false));
+
+        let accept = thread::spawn({
+            let stop = stop.clone();
+            move || {
+                for stream in listener.incoming() {
+                    if stop.load(Ordering::SeqCst) {
+                        break;
+                    }
+                    let Ok(stream) = stream else { continue };
+                    let handler = handler.clone();
+                    thread::spawn(move || serve(stream, &*handler));
+                }
+            }
+        });
+        Ok(Self {
+            url: format!("http://{}", addr),
+            addr,
+            stop,
+            accept: Some(accept),
+        })
+    }
+
+    /// `http://127.0.0.1:{port}`
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    /// A transport sending every request to this server, whatever its host,
+    /// so that clients with a fixed base url can be pointed at it.
+    pub fn transport(&self) -> LocalTransport {
+        LocalTransport {
+            inner: ReqwestTransport::new(),
+            authority: self.url.trim_start_matches("http://").to_string(),
+        }
+    }
+}
+
+impl Drop for MockServer {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::SeqCst);
+        // wake the accept loop up so that it sees the flag and closes the
+        // listener
+        if TcpStream::connect(self.addr).is_ok() {
+            if let Some(accept) = self.accept.take() {
+                let _ = accept.join();
+            }
+        }
+    }
+}
+
+/// See [`MockServer::transport`]
+#[derive(Debug, Clone)]
+pub struct LocalTransport {
+    inner: ReqwestTransport,
+    authority: String,
+}
+
+impl Transport for LocalTransport {
+    async fn send(&self, mut request: Request) -> Result<Response> {
+        let path = request
+            .uri()
+            .path_and_query()
+            .map(|path| path.as_str())
+            .unwrap_or("/");
+        let uri = format!("http://{}{}", self.authority, path);
+        *request.uri_mut() = uri.parse().map_err(http::Error::from)?;
+        self.inner.send(request).await
+    }
+}
+
+/// A torrent served by [`FakeNyaa`], with the metadata [`Torrent`] lacks
+#[derive(Debug, Clone, PartialEq)]
+pub struct FakeTorrent {
+    /// listed in category `1_2` unless it has a category
+    pub torrent: Torrent,
+    pub trusted: bool,
+    pub remake: bool,
+    pub comments: u32,
+    /// `None` for anonymous uploads
+    pub submitter: Option<String>,
+    pub description: String,
+}
+
+impl FakeTorrent {
+    pub fn new(mut torrent: Torrent) -> Self {
+        torrent.category.get_or_insert_with(|| "1_2".into());
+        Self {
+            torrent,
+            trusted: false,
+            remake: false,
+            comments: 0,
+            submitter: None,
+            description: String::new(),
+        }
+    }
+
+    pub fn category<C: Category>(mut self, category: C) -> Self {
+        self.torrent.category = Some(category.to_string());
+        self
+    }
+
+    fn code(&self) -> &str {
+        self.torrent.category.as_deref().unwrap_or("1_2")
+    }
+
+    pub fn trusted(mut self, trusted: bool) -> Self {
+        self.trusted = trusted;
+        self
+    }
+
+    pub fn remake(mut self, remake: bool) -> Self {
+        self.remake = remake;
+        self
+    }
+
+    pub fn comments(mut self, comments: u32) -> Self {
+        self.comments = comments;
+        self
+    }
+
+    pub fn submitter<S: Into<String>>(mut self, submitter: S) -> Self {
+        self.submitter = Some(submitter.into());
+        self
+    }
+
+    pub fn description<S: Into<String>>(mut self, description: S) -> Self {
+        self.description = description.into();
+        self
+    }
+}
+
+impl Filterable for FakeTorrent {
+    fn torrent(&self) -> &Torrent {
+        &self.torrent
+    }
+
+    fn uploader(&self) -> Option<&str> {
+        self.submitter.as_deref()
+    }
+}
+
+impl From<Torrent> for FakeTorrent {
+    fn from(torrent: Torrent) -> Self {
+        Self::new(torrent)
+    }
+}
+
+/// An in-memory NyaaV2 site
+///
+/// Torrents keep the id of their download link, or are numbered in insertion
+/// order when it has none.
+#[derive(Debug, Clone)]
+pub struct FakeNyaa {
+    name: String,
+    per_page: usize,
+    not_found_past_end: bool,
+    torrents: Vec<(TorrentId, FakeTorrent)>,
+}
+
+impl Default for FakeNyaa {
+    fn default() -> Self {
+        Self {
+            name: "Nyaa".into(),
+            per_page: 75,
+            not_found_past_end: false,
+            torrents: Vec::new(),
+        }
+    }
+}
+
+impl FakeNyaa {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Site name used in page titles and feeds
+    pub fn name<S: Into<String>>(mut self, name: S) -> Self {
+        self.name = name.into();
+        self
+    }
+
+    pub fn per_page(mut self, per_page: usize) -> Self {
+        self.per_page = per_page.max(1);
+        self
+    }
+
+    /// Answer pages past the last one with `404 Not Found` rather than an
+    /// empty listing, as nyaa does
+    pub fn not_found_past_end(mut self, not_found: bool) -> Self {
+        self.not_found_past_end = not_found;
+        self
+    }
+
+    pub fn torrent<T: Into<FakeTorrent>>(mut self, torrent: T) -> Self {
+        let torrent = torrent.into();
+        let id = torrent
+            .torrent
+            .id()
+            .unwrap_or(TorrentId(self.torrents.len() as u32 + 1));
+        self.torrents.push((id, torrent));
+        self
+    }
+
+    pub fn torrents<I, T>(self, torrents: I) -> Self
+    where
+        I: IntoIterator<Item = T>,
+        T: Into<FakeTorrent>,
+    {
+        torrents.into_iter().fold(self, FakeNyaa::torrent)
+    }
+
+    /// Serve the site on a local port until the server is dropped
+    pub fn start(self) -> Result<MockServer> {
+        let site = Arc::new(self);
+        MockServer::start(move |req| site.handle(req))
+    }
+
+    fn handle(&self, req: &MockRequest) -> MockResponse {
+        if req.method != "GET" {
+            return MockResponse::status(405);
+        }
+        let route = req.route();
+        let params = req.query();
+
+        if route == "/" {
+            let results = self.search(&params);
+            let page = params.get("p").map_or("1", |p| p.as_str());
+            if self.not_found_past_end && results.is_empty() && page != "1" {
+                return MockResponse::status(404);
+            }
+            return match params.get("page").map(|page| page.as_str()) {
+                Some("rss") => MockResponse::bytes(
+                    "application/xml",
+                    self.render_rss(&results),
+                ),
+                _ => MockResponse::html(self.render_listing(&params, &results)),
+            };
+        }
+        if let Some(id) = route.strip_prefix("/view/") {
+            return match self.find(id) {
+                Some(torrent) => MockResponse::html(self.render_view(torrent)),
+                None => MockResponse::status(404),
+            };
+        }
+        if let Some(file) = route.strip_prefix("/download/") {
+            let torrent =
+                file.strip_suffix(".torrent").and_then(|id| self.find(id));
+            return match torrent {
+                Some(torrent) => MockResponse::bytes(
+                    "application/x-bittorrent",
+                    render_torrent_file(&torrent.1.torrent),
+                ),
+                None => MockResponse::status(404),
+            };
+        }
+        MockResponse::status(404)
+    }
+
+    fn find(&self, id: &str) -> Option<&(TorrentId, FakeTorrent)> {
+        let id: TorrentId = id.parse().ok()?;
+        self.torrents.iter().find(|(other, _)| *other == id)
+    }
+
+    /// Apply `q`, `c`, `f`, `s`, `o` and `p` like nyaa does, which also
+    /// finds torrents by info hash.
+    fn search(
+        &self,
+        params: &HashMap<String, String>,
+    ) -> Vec<&(TorrentId, FakeTorrent)> {
+        let param = |name: &str| params.get(name).map(|v| v.as_str());
+        let terms: Vec<String> = param("q")
+            .unwrap_or_default()
+            .split_whitespace()
+            .map(|term| term.to_lowercase())
+            .collect();
+        let category = param("c").unwrap_or("0_0");
+        let filter = param("f").unwrap_or("0");
+
+        let mut results: Vec<_> = self
+            .torrents
+            .iter()
+            .filter(|(_, fake)| {
+                let title = fake.torrent.title.to_lowercase();
+                let hash = info_hash(&fake.torrent.magnet_url);
+                terms.iter().all(|term| title.contains(term))
+                    || matches!((hash, terms.as_slice()), (Some(hash), [term])
+                        if hash.eq_ignore_ascii_case(term))
+            })
+            .filter(|(_, fake)| match category.strip_suffix("_0") {
+                _ if category == "0_0" => true,
+                Some(main) => fake.code().split('_').next() == Some(main),
+                None => fake.code() == category,
+            })
+            .filter(|(_, fake)| match filter {
+                "1" => !fake.remake,
+                "2" => fake.trusted,
+                _ => true,
+            })
+            .collect();
+
+        results.sort_by(|(a_id, a), (b_id, b)| {
+            let (a_t, b_t) = (&a.torrent, &b.torrent);
+            match param("s").unwrap_or("id") {
+                "comments" => a.comments.cmp(&b.comments),
+                "size" => a_t.size.partial_cmp(&b_t.size).unwrap_or(Equal),
+                "seeders" => a_t.seeders.cmp(&b_t.seeders),
+                "leechers" => a_t.leechers.cmp(&b_t.leechers),
+                "downloads" => a_t.downloads.cmp(&b_t.downloads),
+                _ => a_id.cmp(b_id),
+            }
+        });
+        if param("o") != Some("asc") {
+            results.reverse();
+        }
+
+        let page = param("p")
+            .and_then(|p| p.parse::<usize>().ok())
+            .unwrap_or(1)
+            .max(1);
+        results
+            .into_iter()
+            .skip((page - 1) * self.per_page)
+            .take(self.per_page)
+            .collect()
+    }
+
+    fn render_listing(
+        &self,
+        params: &HashMap<String, String>,
+        results: &[&(TorrentId, FakeTorrent)],
+    ) -> String {
+        let mut body = if results.is_empty() {
+            "<h3>No results found</h3>\n".to_string()
+        } else {
+            let rows: String = results
+                .iter()
+                .map(|(id, fake)| {
+                    let meta = RowMeta {
+                        class: row_class(fake),
+                        comments: fake.comments,
+                    };
+                    let link = format!("/download/{}.torrent", id);
+                    render_row(&fake.torrent, Some(*id), &link, &meta)
+                })
+                .collect();
+            render_table(&rows)
+        };
+        let page = params.get("p").map(|p| p.as_str()).unwrap_or("1");
+        body.push_str(&format!(
+            "<ul class=\"pagination\">\n\
+             <li class=\"active\"><a href=\"#\">{} \
+             <span class=\"sr-only\">(current)</span></a></li>\n\
+             </ul>\n",
+            escape(page)
+        ));
+        render_page(&format!("Browse :: {}", self.name), &body)
+    }
+
+    fn render_view(&self, (id, fake): &(TorrentId, FakeTorrent)) -> String {
+        let torrent = &fake.torrent;
+        let submitter = match &fake.submitter {
+            Some(user) => format!(
+                "<a class=\"text-default\" href=\"/user/{0}\">{0}</a>",
+                escape(user)
+            ),
+            None => "Anonymous".into(),
+        };
+        format!(
+            "<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n\
+             <meta charset=\"utf-8\">\n<title>{title} :: {site}</title>\n\
+             </head>\n<body>\n<div class=\"container\">\n\
+             <div class=\"panel panel-{class}\">\n\
+             <div class=\"panel-heading\">\n\
+             <h3 class=\"panel-title\">{title}</h3>\n</div>\n\
+             <div class=\"panel-body\">\n\
+             <div class=\"row\">\n\
+             <div class=\"col-md-1\">Category:</div>\n\
+             <div class=\"col-md-5\"><a href=\"/?c={category}\">{category}</a></div>\n\
+             <div class=\"col-md-1\">Date:</div>\n\
+             <div class=\"col-md-5\" data-timestamp=\"{timestamp}\">{date}</div>\n\
+             </div>\n<div class=\"row\">\n\
+             <div class=\"col-md-1\">Submitter:</div>\n\
+             <div class=\"col-md-5\">{submitter}</div>\n\
+             <div class=\"col-md-1\">Seeders:</div>\n\
+             <div class=\"col-md-5\"><span style=\"color: green;\">{seeders}</span></div>\n\
+             </div>\n<div class=\"row\">\n\
+             <div class=\"col-md-1\">Information:</div>\n\
+             <div class=\"col-md-5\">No information.</div>\n\
+             <div class=\"col-md-1\">Leechers:</div>\n\
+             <div class=\"col-md-5\"><span style=\"color: red;\">{leechers}</span></div>\n\
+             </div>\n<div class=\"row\">\n\
+             <div class=\"col-md-1\">File size:</div>\n\
+             <div class=\"col-md-5\">{size}</div>\n\
+             <div class=\"col-md-1\">Completed:</div>\n\
+             <div class=\"col-md-5\">{downloads}</div>\n\
+             </div>\n<div class=\"row\">\n\
+             <div class=\"col-md-offset-6 col-md-1\">Info hash:</div>\n\
+             <div class=\"col-md-5\"><kbd>{hash}</kbd></div>\n\
+             </div>\n</div>\n\
+             <div class=\"panel-footer clearfix\">\n\
+             <a href=\"/download/{id}.torrent\"><i class=\"fa fa-download fa-fw\"></i>Download Torrent</a>\n\
+             or <a href=\"{magnet}\" class=\"card-footer-item\"><i class=\"fa fa-magnet fa-fw\"></i>Magnet</a>\n\
+             </div>\n</div>\n\
+             <div class=\"panel panel-default\">\n\
+             <div markdown-text class=\"panel-body\" id=\"torrent-description\">{description}</div>\n\
+             </div>\n\
+             <div id=\"comments\" class=\"panel panel-default\">\n\
+             <div class=\"panel-heading\">\n\
+             <h3 class=\"panel-title\">Comments - {comments}</h3>\n\
+             </div>\n</div>\n</div>\n</body>\n</html>\n",
+            title = escape(&torrent.title),
+            site = escape(&self.name),
+            class = row_class(fake),
+            category = escape(fake.code()),
+            timestamp = torrent.date.timestamp(),
+            date = torrent.date.format("%Y-%m-%d %H:%M UTC"),
+            submitter = submitter,
+            seeders = torrent.seeders,
+            leechers = torrent.leechers,
+            size = torrent.size,
+            downloads = torrent.downloads,
+            hash = info_hash(&torrent.magnet_url).unwrap_or_default(),
+            id = id,
+            magnet = escape(&torrent.magnet_url),
+            description = escape(&fake.description),
+            comments = fake.comments,
+        )
+    }
+
+    fn render_rss(&self, results: &[&(TorrentId, FakeTorrent)]) -> String {
+        let mut rss = format!(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+             <rss xmlns:atom=\"http://www.w3.org/2005/Atom\" \
+             xmlns:nyaa=\"https://nyaa.si/xmlns/nyaa\" version=\"2.0\">\n\
+             <channel>\n<title>{0} - Home - Torrent File RSS</title>\n\
+             <description>RSS Feed for Home</description>\n\
+             <link>/</link>\n",
+            escape(&self.name)
+        );
+        for (id, fake) in results {
+            let torrent = &fake.torrent;
+            let yes_no = |flag: bool| if flag { "Yes" } else { "No" };
+            rss.push_str(&format!(
+                "<item>\n<title>{title}</title>\n\
+                 <link>/download/{id}.torrent</link>\n\
+                 <guid isPermaLink=\"true\">/view/{id}</guid>\n\
+                 <pubDate>{date}</pubDate>\n\
+                 <nyaa:seeders>{seeders}</nyaa:seeders>\n\
+                 <nyaa:leechers>{leechers}</nyaa:leechers>\n\
+                 <nyaa:downloads>{downloads}</nyaa:downloads>\n\
+                 <nyaa:infoHash>{hash}</nyaa:infoHash>\n\
+                 <nyaa:categoryId>{category}</nyaa:categoryId>\n\
+                 <nyaa:size>{size}</nyaa:size>\n\
+                 <nyaa:comments>{comments}</nyaa:comments>\n\
+                 <nyaa:trusted>{trusted}</nyaa:trusted>\n\
+                 <nyaa:remake>{remake}</nyaa:remake>\n\
+                 </item>\n",
+                title = escape(&torrent.title),
+                id = id,
+                date = torrent.date.format("%a, %d %b %Y %H:%M:%S -0000"),
+                seeders = torrent.seeders,
+                leechers = torrent.leechers,
+                downloads = torrent.downloads,
+                hash = info_hash(&torrent.magnet_url).unwrap_or_default(),
+                category = escape(fake.code()),
+                size = torrent.size,
+                comments = fake.comments,
+                trusted = yes_no(fake.trusted),
+                remake = yes_no(fake.remake),
+            ));
+        }
+        rss.push_str("</channel>\n</rss>\n");
+        rss
+    }
+}
+
+fn row_class(fake: &FakeTorrent) -> &'static str {
+    if fake.remake {
+        "danger"
+    } else if fake.trusted {
+        "success"
+    } else {
+        "default"
+    }
+}
+
+/// A minimal single-file `.torrent`, bencoded.
+fn render_torrent_file(torrent: &Torrent) -> Vec<u8> {
+    let length = match torrent.size {
+        Size::KB(value) => value as f64 * 1024.0,
+        Size::MB(value) => value as f64 * 1024.0 * 1024.0,
+        Size::GB(value) => value as f64 * 1024.0 * 1024.0 * 1024.0,
+        Size::TB(value) => value as f64 * 1024.0 * 1024.0 * 1024.0 * 1024.0,
+    } as u64;
+    let tracker = "http://nyaa.tracker.wf:7777/announce";
+    let mut file = format!(
+        "d8:announce{}:{}4:infod6:lengthi{}e4:name{}:{}\
+         12:piece lengthi262144e6:pieces20:",
+        tracker.len(),
+        tracker,
+        length,
+        torrent.title.len(),
+        torrent.title
+    )
+    .into_bytes();
+    file.extend_from_slice(&[0; 20]);
+    file.extend_from_slice(b"ee");
+    file
+}
+
+fn info_hash(magnet: &str) -> Option<&str> {
+    let start = magnet.find("urn:btih:")? + "urn:btih:".len();
+    magnet[start..].split('&').next()
+}
+
+fn serve(stream: TcpStream, handler: &Handler) {
+    // a client that never sends its request can't keep the thread around
+    let _ = stream.set_read_timeout(Some(Duration::from_secs(10)));
+    let mut reader = BufReader::new(stream);
+    let mut line = String::new();
+    if reader.read_line(&mut line).is_err() {
+        return;
+    }
+    let mut parts = line.split_whitespace();
+    let method = parts.next().unwrap_or_default().to_string();
+    let path = parts.next().unwrap_or_default().to_string();
+
+    let mut headers: HashMap<String, String> = HashMap::new();
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line).is_err() || line.trim().is_empty() {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            headers
+                .insert(name.trim().to_ascii_lowercase(), value.trim().into());
+        }
+    }
+
+    let length = headers
+        .get("content-length")
+        .and_then(|len| len.parse::<usize>().ok())
+        .unwrap_or(0);
+    let mut body = vec![0; length];
+    if reader.read_exact(&mut body).is_err() {
+        return;
+    }
+
+    let request = MockRequest {
+        method,
+        path,
+        headers,
+        body,
+    };
+    let response = handler(&request);
+
+    let mut stream = reader.into_inner();
+    let mut head = format!("HTTP/1.1 {} Mock\r\n", response.status);
+    for (name, value) in &response.headers {
+        head.push_str(&format!("{name}: {value}\r\n"));
+    }
+    head.push_str(&format!(
+        "Content-Length: {}\r\nConnection: close\r\n\r\n",
+        response.body.len()
+    ));
+    let _ = stream.write_all(head.as_bytes());
+    let _ = stream.write_all(&response.body);
+}
+
+fn decode_pairs(s: &str) -> HashMap<String, String> {
+    s.split('&')
+        .filter_map(|pair| pair.split_once('='))
+        .map(|(k, v)| (decode(k), decode(v)))
+        .collect()
+}
+
+fn decode(s: &str) -> String {
+    let bytes = s.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'+' => out.push(b' '),
+            b'%' if i + 2 < bytes.len() => {
+                let hex = std::str::from_utf8(&bytes[i + 1..i + 3]).ok();
+                match hex.and_then(|hex| u8::from_str_radix(hex, 16).ok()) {
+                    Some(byte) => {
+                        out.push(byte);
+                        i += 2;
+                    }
+                    None => out.push(b'%'),
+                }
+            }
+            byte => out.push(byte),
+        }
+        i += 1;
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack
+        .windows(needle.len())
+        .position(|window| window == needle)
+}
+
+fn split<'a>(mut body: &'a [u8], separator: &[u8]) -> Vec<&'a [u8]> {
+    let mut parts = Vec::new();
+    while let Some(pos) = find(body, separator) {
+        parts.push(&body[..pos]);
+        body = &body[pos + separator.len()..];
+    }
+    parts.push(body);
+    parts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock;
+    use crate::transport::{request, RequestBuilderExt, ResponseExt};
+    use crate::{query::Filter, SortOrder};
+    use crate::{Client, NyaaCategory, NyaaClient, QueryBuilder, Sort};
+    use http::Method;
+
+    fn torrent(id: u32, title: &str, size: Size, seeders: u32) -> Torrent {
+        mock::torrent(id)
+            .title(title)
+            .size(size)
+            .seeders(seeders)
+            .build()
+    }
+
+    fn site() -> FakeNyaa {
+        FakeNyaa::new().per_page(2).torrents([
+            FakeTorrent::new(torrent(1, "[A] Show - 01", Size::GB(1.0), 5))
+                .category(NyaaCategory::AnimeEnglishTranslated)
+                .trusted(true)
+                .comments(3)
+                .submitter("alice"),
+            FakeTorrent::new(torrent(
+                2,
+                "[B] Show - 01 <v2>",
+                Size::MB(700.0),
+                9,
+            ))
+            .category(NyaaCategory::AnimeRaw)
+            .remake(true),
+            FakeTorrent::new(torrent(3, "[A] Show - 02", Size::GB(1.1), 7))
+                .category(NyaaCategory::AnimeEnglishTranslated)
+                .trusted(true),
+            FakeTorrent::new(torrent(4, "Some Album", Size::MB(300.0), 1))
+                .category(NyaaCategory::AudioLossless),
+        ])
+    }
+
+    #[test]
+    fn stop_serving_when_dropped() {
+        let server = MockServer::start(|_| MockResponse::status(204)).unwrap();
+        let addr = server.addr;
+        assert!(TcpStream::connect(addr).is_ok());
+
+        drop(server);
+        assert!(TcpStream::connect(addr).is_err());
+    }
+
+    #[tokio::test]
+    async fn search_the_fake_site() {
+        let server = site().start().unwrap();
+        let client = NyaaClient::with_transport(server.transport());
+        let titles = |torrents: Vec<Torrent>| -> Vec<String> {
+            torrents.into_iter().map(|t| t.title).collect()
+        };
+
+        let query = QueryBuilder::new().search("show").build();
+        let res = client.get(&query).await.unwrap();
+        assert_eq!(titles(res), ["[B] Show - 01 <v2>", "[A] Show - 02"]);
+
+        let query = QueryBuilder::new().search("show").page(2).build();
+        let res = client.get(&query).await.unwrap();
+        assert_eq!(res.len(), 1);
+        assert_eq!(res[0].link, "https://nyaa.si/download/1.torrent");
+        assert_eq!(res[0].size, Size::GB(1.0));
+
+        let query = QueryBuilder::new()
+            .category(NyaaCategory::Anime)
+            .filter(Filter::NoRemakes)
+            .sort(Sort::Size)
+            .sort_order(SortOrder::Asecending)
+            .build();
+        let res = client.get(&query).await.unwrap();
+        assert_eq!(titles(res), ["[A] Show - 01", "[A] Show - 02"]);
+
+        let query = QueryBuilder::new()
+            .category(NyaaCategory::Audio)
+            .filter(Filter::TrustedOnly)
+            .build();
+        assert!(client.get(&query).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn serve_view_rss_and_downloads() {
+        let server = site().start().unwrap();
+        let transport = server.transport();
+        let get = |path: &str| {
+            let url = format!("https://nyaa.si{}", path);
+            transport.send(request(Method::GET, &url).empty().unwrap())
+        };
+
+        let view = get("/view/2").await.unwrap();
+        assert!(view.text().contains("[B] Show - 01 &lt;v2&gt;"));
+        assert_eq!(get("/view/9").await.unwrap().status(), 404);
+
+        let client = NyaaClient::with_transport(transport.clone());
+        assert_eq!(
+            client.uploader(TorrentId(1)).await.unwrap().as_deref(),
+            Some("alice")
+        );
+        assert_eq!(client.uploader(TorrentId(2)).await.unwrap(), None);
+
+        let rss = get("/?page=rss&c=1_2").await.unwrap().text();
+        assert_eq!(rss.matches("<item>").count(), 2);
+        assert!(rss.contains("<nyaa:trusted>Yes</nyaa:trusted>"));
+
+        let file = get("/download/4.torrent").await.unwrap();
+        assert!(file.body().starts_with(b"d8:announce"));
+        assert!(file.text().contains("10:Some Album"));
+    }
+}
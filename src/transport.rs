@@ -0,0 +1,181 @@
+use std::future::Future;
+
+use base64::Engine;
+use http::header::{AUTHORIZATION, CONTENT_TYPE};
+use http::request::Builder;
+use http::Method;
+use serde::Serialize;
+
+use crate::error::{Error, Result};
+use crate::multipart::Multipart;
+
+pub type Request = http::Request<Vec<u8>>;
+pub type Response = http::Response<Vec<u8>>;
+
+/// The HTTP stack the clients send their requests through
+///
+/// Implementations must not follow redirects: sessions read the `Location`
+/// and `Set-Cookie` headers of redirect responses themselves.
+pub trait Transport: Send + Sync {
+    fn send(
+        &self,
+        request: Request,
+    ) -> impl Future<Output = Result<Response>> + Send;
+}
+
+impl<T: Transport> Transport for &T {
+    fn send(
+        &self,
+        request: Request,
+    ) -> impl Future<Output = Result<Response>> + Send {
+        (**self).send(request)
+    }
+}
+
+impl<T: Transport> Transport for std::sync::Arc<T> {
+    fn send(
+        &self,
+        request: Request,
+    ) -> impl Future<Output = Result<Response>> + Send {
+        (**self).send(request)
+    }
+}
+
+/// The default transport, backed by `reqwest`
+///
+/// Like `reqwest` itself it must run inside a Tokio runtime, other runtimes
+/// need their own [`Transport`].
+#[derive(Debug, Clone)]
+pub struct ReqwestTransport {
+    inner: reqwest::Client,
+}
+
+impl ReqwestTransport {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Use a preconfigured client, which should have redirects disabled
+    pub fn from_client(inner: reqwest::Client) -> Self {
+        Self { inner }
+    }
+}
+
+impl Default for ReqwestTransport {
+    fn default() -> Self {
+        let inner = reqwest::Client::builder()
+            .redirect(reqwest::redirect::Policy::none())
+            .build()
+            .unwrap_or_default();
+        Self { inner }
+    }
+}
+
+impl Transport for ReqwestTransport {
+    async fn send(&self, request: Request) -> Result<Response> {
+        let res = self.inner.execute(request.try_into()?).await?;
+
+        let mut builder = http::Response::builder()
+            .status(res.status())
+            .version(res.version());
+        if let Some(headers) = builder.headers_mut() {
+            *headers = res.headers().clone();
+        }
+        Ok(builder.body(res.bytes().await?.to_vec())?)
+    }
+}
+
+/// Start a request to `url`, percent-encoding characters `http` rejects.
+pub(crate) fn request(method: Method, url: &str) -> Builder {
+    let builder = http::Request::builder().method(method);
+    match reqwest::Url::parse(url) {
+        Ok(url) => builder.uri(url.as_str()),
+        Err(_) => builder.uri(url),
+    }
+}
+
+/// Finishing a [`Builder`] with the bodies the clients send
+pub(crate) trait RequestBuilderExt: Sized {
+    fn empty(self) -> Result<Request>;
+
+    fn form<F: Serialize + ?Sized>(self, form: &F) -> Result<Request>;
+
+    fn multipart(self, multipart: Multipart) -> Result<Request>;
+
+    fn basic_auth(self, username: &str, password: &str) -> Self;
+}
+
+impl RequestBuilderExt for Builder {
+    fn empty(self) -> Result<Request> {
+        Ok(self.body(Vec::new())?)
+    }
+
+    fn form<F: Serialize + ?Sized>(self, form: &F) -> Result<Request> {
+        let body = serde_urlencoded::to_string(form)
+            .map_err(|err| Error::FormError(err.to_string()))?;
+        Ok(self
+            .header(CONTENT_TYPE, "application/x-www-form-urlencoded")
+            .body(body.into_bytes())?)
+    }
+
+    fn multipart(self, multipart: Multipart) -> Result<Request> {
+        Ok(self
+            .header(CONTENT_TYPE, multipart.content_type())
+            .body(multipart.finish())?)
+    }
+
+    fn basic_auth(self, username: &str, password: &str) -> Self {
+        let credentials = base64::engine::general_purpose::STANDARD
+            .encode(format!("{}:{}", username, password));
+        self.header(AUTHORIZATION, format!("Basic {}", credentials))
+    }
+}
+
+pub(crate) trait ResponseExt {
+    fn text(&self) -> String;
+}
+
+impl ResponseExt for Response {
+    fn text(&self) -> String {
+        String::from_utf8_lossy(self.body()).into_owned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::LISTING;
+    use crate::{Client, NyaaClient, QueryBuilder};
+
+    /// Answers every request with the same page
+    struct StaticTransport(&'static str);
+
+    impl Transport for StaticTransport {
+        async fn send(&self, request: Request) -> Result<Response> {
+            assert_eq!(
+                request.uri().to_string(),
+                "https://nyaa.si/?q=show%20one&p=1&s=seeders&o=desc&f=0&c=0_0"
+            );
+            Ok(http::Response::new(self.0.as_bytes().to_vec()))
+        }
+    }
+
+    #[tokio::test]
+    async fn client_over_custom_transport() {
+        let client = NyaaClient::with_transport(StaticTransport(LISTING));
+        let query = QueryBuilder::new().search("show one").build();
+
+        let res = client.get(&query).await.unwrap();
+        assert_eq!(res[0].link, "https://nyaa.si/download/1.torrent");
+    }
+
+    #[test]
+    fn encode_bodies() {
+        let req = request(Method::POST, "http://localhost/login")
+            .basic_auth("user", "pw")
+            .form(&[("a", "b c"), ("d", "&")])
+            .unwrap();
+        assert_eq!(req.body(), b"a=b+c&d=%26");
+        assert_eq!(req.headers()[AUTHORIZATION], "Basic dXNlcjpwdw==");
+    }
+}
@@ -0,0 +1,376 @@
+use http::header::LOCATION;
+use serde::{Deserialize, Serialize};
+use tracing::{event, Level};
+
+use crate::{
+    category::{split_code, Category},
+    error::{Error, Result},
+    form::{error_message, HtmlForm},
+    model::TorrentId,
+    multipart::Multipart,
+    session::Session,
+    transport::{RequestBuilderExt, ResponseExt, Transport},
+};
+
+const TORRENT_MIME: &str = "application/x-bittorrent";
+
+/// A torrent to be uploaded to the site of category `C`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Upload<C> {
+    torrent_file: Vec<u8>,
+    file_name: String,
+    name: String,
+    category: C,
+    information: String,
+    description: String,
+    anonymous: bool,
+    hidden: bool,
+    remake: bool,
+    complete: bool,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct UploadBuilder<C> {
+    torrent_file: Vec<u8>,
+    file_name: String,
+    name: String,
+    category: C,
+    information: String,
+    description: String,
+    anonymous: bool,
+    hidden: bool,
+    remake: bool,
+    complete: bool,
+}
+
+impl<C: Category> UploadBuilder<C> {
+    /// Start an upload of the given `.torrent` file contents to `category`,
+    /// which must be a sub-category like `1_2`
+    pub fn new<B: Into<Vec<u8>>>(
+        torrent_file: B,
+        category: C,
+    ) -> UploadBuilder<C> {
+        UploadBuilder {
+            torrent_file: torrent_file.into(),
+            file_name: "upload.torrent".into(),
+            name: String::new(),
+            category,
+            information: String::new(),
+            description: String::new(),
+            anonymous: false,
+            hidden: false,
+            remake: false,
+            complete: false,
+        }
+    }
+
+    pub fn build(self) -> Upload<C> {
+        Upload {
+            torrent_file: self.torrent_file,
+            file_name: self.file_name,
+            name: self.name,
+            category: self.category,
+            information: self.information,
+            description: self.description,
+            anonymous: self.anonymous,
+            hidden: self.hidden,
+            remake: self.remake,
+            complete: self.complete,
+        }
+    }
+
+    pub fn file_name<S: Into<String>>(mut self, file_name: S) -> Self {
+        self.file_name = file_name.into();
+        self
+    }
+
+    /// Display name, the torrent's own name is used when empty
+    pub fn name<S: Into<String>>(mut self, name: S) -> Self {
+        self.name = name.into();
+        self
+    }
+
+    /// Information url, e.g. the release group's website
+    pub fn information<S: Into<String>>(mut self, information: S) -> Self {
+        self.information = information.into();
+        self
+    }
+
+    pub fn description<S: Into<String>>(mut self, description: S) -> Self {
+        self.description = description.into();
+        self
+    }
+
+    pub fn anonymous(mut self, anonymous: bool) -> Self {
+        self.anonymous = anonymous;
+        self
+    }
+
+    pub fn hidden(mut self, hidden: bool) -> Self {
+        self.hidden = hidden;
+        self
+    }
+
+    pub fn remake(mut self, remake: bool) -> Self {
+        self.remake = remake;
+        self
+    }
+
+    pub fn complete(mut self, complete: bool) -> Self {
+        self.complete = complete;
+        self
+    }
+}
+
+/// `torrent_data` of the `/api/upload` endpoint
+#[derive(Debug, Serialize)]
+struct ApiTorrentData<'a> {
+    name: &'a str,
+    category: String,
+    information: &'a str,
+    description: &'a str,
+    anonymous: bool,
+    hidden: bool,
+    complete: bool,
+    remake: bool,
+}
+
+#[derive(Debug, Deserialize)]
+struct ApiResponse {
+    id: Option<u32>,
+    #[serde(default)]
+    errors: serde_json::Value,
+}
+
+impl<C: Category, T: Transport> Session<C, T> {
+    /// Upload a torrent through the web form, returning its id
+    #[tracing::instrument(skip_all)]
+    pub async fn upload(&self, upload: &Upload<C>) -> Result<TorrentId> {
+        check_category(&upload.category)?;
+        let page = self.fetch("/upload").await?;
+        let form = HtmlForm::find(&page, "torrent_file")?;
+
+        let mut multipart = Multipart::new()
+            .text("csrf_token", form.csrf_token()?)
+            .file(
+                "torrent_file",
+                &upload.file_name,
+                TORRENT_MIME,
+                &upload.torrent_file,
+            )
+            .text("display_name", &upload.name)
+            .text("category", &upload.category.to_string())
+            .text("information", &upload.information)
+            .text("description", &upload.description);
+        for (field, set) in [
+            ("is_anonymous", upload.anonymous),
+            ("is_hidden", upload.hidden),
+            ("is_remake", upload.remake),
+            ("is_complete", upload.complete),
+        ] {
+            if set {
+                multipart = multipart.text(field, "y");
+            }
+        }
+
+        let res = self
+            .send(self.post("/upload").multipart(multipart)?)
+            .await?;
+
+        if res.status().is_redirection() {
+            let location = res
+                .headers()
+                .get(LOCATION)
+                .and_then(|location| location.to_str().ok())
+                .unwrap_or_default();
+            event!(Level::DEBUG, "uploaded to {}", location);
+            return TorrentId::from_url(location).ok_or_else(|| {
+                Error::UploadError(format!("Unexpected redirect: {location}"))
+            });
+        }
+        let page = res.text();
+        Err(Error::UploadError(
+            error_message(&page).unwrap_or_else(|| "Upload failed".into()),
+        ))
+    }
+
+    /// Upload a torrent through the NyaaV2 `/api/upload` endpoint, which
+    /// authenticates each request with a username and password rather than
+    /// the session's cookies
+    #[tracing::instrument(skip_all)]
+    pub async fn upload_via_api(
+        &self,
+        upload: &Upload<C>,
+        username: &str,
+        password: &str,
+    ) -> Result<TorrentId> {
+        check_category(&upload.category)?;
+        let torrent_data = serde_json::to_string(&ApiTorrentData {
+            name: &upload.name,
+            category: upload.category.to_string(),
+            information: &upload.information,
+            description: &upload.description,
+            anonymous: upload.anonymous,
+            hidden: upload.hidden,
+            complete: upload.complete,
+            remake: upload.remake,
+        })?;
+
+        let multipart = Multipart::new()
+            .file(
+                "torrent",
+                &upload.file_name,
+                TORRENT_MIME,
+                &upload.torrent_file,
+            )
+            .text("torrent_data", &torrent_data);
+        let res = self
+            .send_raw(
+                self.post("/api/upload")
+                    .basic_auth(username, password)
+                    .multipart(multipart)?,
+            )
+            .await?;
+
+        match serde_json::from_slice::<ApiResponse>(res.body()) {
+            Ok(ApiResponse { id: Some(id), .. }) => Ok(TorrentId(id)),
+            Ok(ApiResponse { errors, .. }) if !errors.is_null() => {
+                Err(Error::UploadError(errors.to_string()))
+            }
+            _ => Err(Error::StatusError(res.status().as_u16())),
+        }
+    }
+}
+
+/// The site only takes uploads to sub-categories, `All` and the main
+/// categories are shown but can't be picked
+fn check_category<C: Category>(category: &C) -> Result<()> {
+    match split_code(category.code()) {
+        (_, "0") => Err(Error::UploadError(format!(
+            "Can't upload to {}, pick a sub-category",
+            category.name()
+        ))),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::{MockResponse, MockServer};
+    use crate::{NyaaCategory, SukebeiCategory};
+
+    const UPLOAD_PAGE: &str = r#"<nav class="navbar">
+    <form class="navbar-form navbar-right form" action="/" method="get">
+        <input type="text" class="form-control search-bar" name="q" value="">
+    </form>
+    </nav>
+    <form method="POST" enctype="multipart/form-data">
+        <input id="csrf_token" name="csrf_token" type="hidden" value="up">
+        <input id="torrent_file" name="torrent_file" type="file">
+    </form>"#;
+
+    fn upload_server() -> MockServer {
+        MockServer::start(|req| {
+            match (req.method.as_str(), req.path.as_str()) {
+                ("GET", "/login") | ("GET", "/upload") => {
+                    MockResponse::html(UPLOAD_PAGE)
+                }
+                ("POST", "/login") => MockResponse::redirect("/"),
+                ("POST", "/upload") => {
+                    let form = req.multipart();
+                    assert_eq!(form["csrf_token"], b"up");
+                    assert_eq!(form["torrent_file"], b"d4:infode");
+                    assert_eq!(form["category"], b"1_2");
+                    assert_eq!(form["is_complete"], b"y");
+                    assert!(!form.contains_key("is_hidden"));
+                    if form["display_name"].is_empty() {
+                        MockResponse::html(
+                            r#"<div class="alert alert-danger">Name missing</div>"#,
+                        )
+                    } else {
+                        MockResponse::redirect("/view/1234")
+                    }
+                }
+                ("POST", "/api/upload") => {
+                    // base64 of "user:pw"
+                    assert_eq!(
+                        req.header("authorization"),
+                        Some("Basic dXNlcjpwdw==")
+                    );
+                    let form = req.multipart();
+                    let data: serde_json::Value =
+                        serde_json::from_slice(&form["torrent_data"]).unwrap();
+                    assert_eq!(data["category"], "1_3");
+                    assert_eq!(data["hidden"], true);
+                    MockResponse::html(r#"{"id": 99, "url": "/view/99"}"#)
+                }
+                _ => MockResponse::status(404),
+            }
+        }).unwrap()
+    }
+
+    #[tokio::test]
+    async fn upload_through_form() {
+        let server = upload_server();
+        let session = Session::<NyaaCategory>::new(server.url());
+        let upload = UploadBuilder::new(
+            b"d4:infode".to_vec(),
+            NyaaCategory::AnimeEnglishTranslated,
+        )
+        .name("[Group] Show - 01")
+        .complete(true)
+        .build();
+
+        let id = session.upload(&upload).await.unwrap();
+        assert_eq!(id, TorrentId(1234));
+    }
+
+    #[tokio::test]
+    async fn upload_form_errors() {
+        let server = upload_server();
+        let session = Session::<NyaaCategory>::new(server.url());
+        let upload = UploadBuilder::new(
+            b"d4:infode".to_vec(),
+            NyaaCategory::AnimeEnglishTranslated,
+        )
+        .complete(true)
+        .build();
+
+        let err = session.upload(&upload).await.unwrap_err();
+        assert_eq!(err.to_string(), "Upload error: Name missing");
+    }
+
+    #[tokio::test]
+    async fn upload_via_api() {
+        let server = upload_server();
+        let session = Session::<SukebeiCategory>::new(server.url());
+        let upload = UploadBuilder::new(
+            b"d4:infode".to_vec(),
+            SukebeiCategory::ArtGames,
+        )
+        .hidden(true)
+        .build();
+
+        let id = session.upload_via_api(&upload, "user", "pw").await.unwrap();
+        assert_eq!(id, TorrentId(99));
+    }
+
+    #[tokio::test]
+    async fn reject_main_categories() {
+        let server = upload_server();
+        let session = Session::<NyaaCategory>::new(server.url());
+
+        for category in [NyaaCategory::All, NyaaCategory::Anime] {
+            let upload =
+                UploadBuilder::new(b"d4:infode".to_vec(), category).build();
+            let err = session.upload(&upload).await.unwrap_err();
+            assert!(matches!(err, Error::UploadError(_)));
+            let err = session
+                .upload_via_api(&upload, "user", "pw")
+                .await
+                .unwrap_err();
+            assert!(matches!(err, Error::UploadError(_)));
+        }
+    }
+}